@@ -0,0 +1,141 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use ban_binance_bot::{config::Archive, util::text::simhash};
+use tempfile::NamedTempFile;
+
+/// Persistent archive of SimHash signatures of banned message text.
+///
+/// Spam reworded in a few places has a signature close to the original, and is recognized as
+/// near duplicate. Signatures are kept in memory, and written to a JSON file on each change if a
+/// path is set.
+#[derive(Clone, Default)]
+pub struct SpamArchive {
+    /// File the signatures are stored in.
+    path: Option<PathBuf>,
+
+    /// Signatures, oldest first.
+    signatures: Arc<RwLock<VecDeque<u64>>>,
+}
+
+impl SpamArchive {
+    /// Load the archive from the given file.
+    ///
+    /// If the file doesn't exist yet, the archive starts empty.
+    pub fn load<P: Into<PathBuf>>(path: P) -> Result<Self, Error> {
+        let path = path.into();
+        let signatures = match fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data).map_err(Error::Json)?,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => VecDeque::new(),
+            Err(err) => return Err(Error::Io(err)),
+        };
+
+        Ok(Self {
+            path: Some(path),
+            signatures: Arc::new(RwLock::new(signatures)),
+        })
+    }
+
+    /// Find the Hamming distance to the closest archived signature of the given words.
+    ///
+    /// Returns `None` if no signature is within the configured distance, or if there are too few
+    /// words.
+    pub fn find<S: AsRef<str>>(&self, config: &Archive, words: &[S]) -> Option<u32> {
+        if config.max_size == 0 || words.len() < config.min_words {
+            return None;
+        }
+
+        let signature = simhash(words);
+        self.signatures
+            .read()
+            .unwrap()
+            .iter()
+            .map(|other| (signature ^ other).count_ones())
+            .filter(|distance| *distance <= config.distance)
+            .min()
+    }
+
+    /// Archive the signature of the given words, and persist the archive.
+    ///
+    /// Nothing is archived if there are too few words, or if the signature is archived already.
+    pub fn insert<S: AsRef<str>>(&self, config: &Archive, words: &[S]) -> Result<(), Error> {
+        if config.max_size == 0 || words.len() < config.min_words {
+            return Ok(());
+        }
+
+        let signature = simhash(words);
+        let mut signatures = self.signatures.write().unwrap();
+        if signatures.contains(&signature) {
+            return Ok(());
+        }
+        signatures.push_back(signature);
+        while signatures.len() > config.max_size {
+            signatures.pop_front();
+        }
+
+        if let Some(path) = &self.path {
+            save(path, &signatures)?;
+        }
+        Ok(())
+    }
+}
+
+/// Write the given signatures to a file.
+///
+/// A temporary file is written first and then moved into place, so the file is never left
+/// partially written.
+fn save(path: &Path, signatures: &VecDeque<u64>) -> Result<(), Error> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let mut file = NamedTempFile::new_in(dir).map_err(Error::Io)?;
+    let data = serde_json::to_vec(signatures).map_err(Error::Json)?;
+    file.write_all(&data).map_err(Error::Io)?;
+    file.persist(path).map_err(|err| Error::Io(err.error))?;
+    Ok(())
+}
+
+/// A spam archive error.
+#[derive(Debug)]
+pub enum Error {
+    /// Failed to read or write the archive file.
+    Io(io::Error),
+
+    /// Failed to parse or serialize the archive file.
+    Json(serde_json::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ban_binance_bot::util::text::words;
+
+    #[test]
+    fn test_archive() {
+        let config = Archive::default();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("archive.json");
+        let spam = words(
+            "Binance is celebrating the launch of its new exchange! The first 5000 participants \
+            receive a deposit bonus, send BTC to the address below to join the event",
+        );
+        let tweaked = words(
+            "Binance is celebrating the launch of its new exchange! The first 3000 participants \
+            get a deposit bonus, send BTC to the address below to join the giveaway",
+        );
+
+        let archive = SpamArchive::load(&path).unwrap();
+        assert_eq!(archive.find(&config, &spam), None);
+        archive.insert(&config, &spam).unwrap();
+        assert_eq!(archive.find(&config, &spam), Some(0));
+
+        // Signatures survive a restart
+        let archive = SpamArchive::load(&path).unwrap();
+        assert_eq!(archive.find(&config, &tweaked), Some(4));
+        assert_eq!(archive.find(&config, &words("Binance new exchange")), None);
+    }
+}
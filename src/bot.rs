@@ -1,37 +1,148 @@
-use std::env;
 use std::time::Duration;
 
-use futures::prelude::*;
+use ban_binance_bot::{
+    config::Action,
+    scanner::{
+        self,
+        score::select_verdicts,
+        verdict::{Kind, Rule},
+        Verdict,
+    },
+    util::{self, text::skeleton},
+};
+use futures::{
+    future::{self, Either},
+    prelude::*,
+    stream,
+};
 use telegram_bot::{
     prelude::*,
-    types::{ChatId, Message, MessageChat, MessageKind, ParseMode, Update, UpdateKind},
+    types::{
+        ChatId, Error as RawError, HttpRequest as TelegramRequest, JsonRequestType,
+        JsonTrueToUnitResponse, Message, MessageChat, MessageKind, ParseMode, Request,
+        RequestType, RequestUrl, Update, UpdateKind, UserId,
+    },
     Error as TelegramError,
 };
 use tokio::timer::delay_for;
-use took::Timer;
+use took::{Timer, Took};
 
 use crate::{
-    config::*,
-    scanner,
+    commands::{self, Command},
+    files,
+    fingerprints::Fingerprint,
+    retry::{Backoff, ErrorClass},
+    signal,
     state::State,
     traits::ChatUsername,
-    util::{self, future::select_true},
+    webhook,
 };
 
 /// Build a future for handling Telegram API updates.
+///
+/// Updates are received through a webhook if configured, or through long polling otherwise.
+/// This completes after a shutdown signal is received.
 pub async fn build_telegram_handler(state: State) -> Result<(), UpdateError> {
-    // Fetch new updates via long poll method, buffer to handle updates concurrently
-    let mut stream = state
-        .telegram_client()
-        .stream()
+    run_telegram_handler(state, signal::shutdown()).await
+}
+
+/// Handle Telegram API updates, until the given shutdown future completes.
+async fn run_telegram_handler<F>(state: State, shutdown: F) -> Result<(), UpdateError>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let shutdown = shutdown.boxed().shared();
+    let updates = match state.config().telegram.webhook.clone() {
+        Some(config) => {
+            // Start the webhook listener, then register it with Telegram
+            let (updates, server) =
+                webhook::listen(config.clone(), shutdown.clone()).map_err(UpdateError::Webhook)?;
+            tokio::spawn(server.unwrap_or_else(|err| error!("Telegram webhook listener failed: {}", err)));
+            webhook::register(&state, &config)
+                .await
+                .map_err(UpdateError::Telegram)?;
+
+            // The listener stops on shutdown, updates it already accepted are still handled
+            updates.map(Ok).boxed()
+        }
+        None => {
+            // Updates can't be polled while a webhook is registered
+            webhook::remove(&state).await.map_err(UpdateError::Telegram)?;
+
+            // Stop taking new updates once a shutdown signal is received
+            stream::select(
+                state.telegram_client().stream().map(Some),
+                shutdown.clone().into_stream().map(|_| None),
+            )
+            .take_while(|update| future::ready(update.is_some()))
+            .map(Option::unwrap)
+            .boxed()
+        }
+    };
+
+    // Buffer to handle updates concurrently, finish in-flight updates when shutting down
+    let run = handle_updates(state.clone(), updates);
+
+    // Limit time to finish in-flight updates after a shutdown signal
+    let timeout = Duration::from_secs(state.config().general.shutdown_timeout);
+    let deadline = shutdown.then(move |_| delay_for(timeout));
+
+    let result = match future::select(run.boxed(), deadline.boxed()).await {
+        Either::Left((result, _)) => result,
+        Either::Right(_) => {
+            warn!("Shutdown timeout reached, dropping in-flight updates");
+            Ok(())
+        }
+    };
+
+    // Run pending self-destructs and annotations before quitting
+    state.scheduler().flush(timeout).await;
+
+    // Keep URL audits for the next run
+    if let Err(err) = state.context().urls.save() {
+        warn!("Failed to save URL cache: {:?}", err);
+    }
+
+    result
+}
+
+/// Handle all updates from the given stream, until the stream ends.
+///
+/// Updates are handled concurrently. Errors are recovered from where possible.
+async fn handle_updates<S>(state: State, updates: S) -> Result<(), UpdateError>
+where
+    S: Stream<Item = Result<Update, TelegramError>>,
+{
+    let concurrent = state.config().general.concurrent_updates;
+    let mut stream = updates
         .map(|update| handle_update(state.clone(), update))
-        .buffer_unordered(*TELEGRAM_CONCURRENT_UPDATES);
+        .buffer_unordered(concurrent);
 
-    // Run the update stream to completion
+    // Run the update stream to completion, recover from errors where possible
+    let mut backoff = Backoff::default();
     while let Some(update) = stream.next().await {
-        // Return errors
-        if update.is_err() {
-            return update;
+        match update {
+            Ok(()) => backoff.reset(),
+            Err(UpdateError::Telegram(err)) => match ErrorClass::of(&err) {
+                ErrorClass::Fatal => {
+                    error!("Fatal Telegram API error, stopping: {}", err);
+                    return Err(UpdateError::Telegram(err));
+                }
+                ErrorClass::FloodWait(seconds) => {
+                    warn!("Telegram API flood wait, retrying in {} seconds: {}", seconds, err);
+                    delay_for(Duration::from_secs(seconds)).await;
+                }
+                ErrorClass::Server | ErrorClass::Network => {
+                    let delay = backoff.next_delay();
+                    warn!(
+                        "Failed to receive Telegram API updates, retrying in {} seconds: {}",
+                        delay.as_secs(),
+                        err,
+                    );
+                    delay_for(delay).await;
+                }
+            },
+            Err(err) => warn!("Failed to handle Telegram API update, skipping: {:?}", err),
         }
     }
 
@@ -39,12 +150,14 @@ pub async fn build_telegram_handler(state: State) -> Result<(), UpdateError> {
 }
 
 /// Handle the given Telegram API update.
+///
+/// Errors from receiving the update are returned as `UpdateError::Telegram`, failures while
+/// handling a received update are returned as `UpdateError::Other`.
 async fn handle_update(
     state: State,
     update: Result<Update, TelegramError>,
 ) -> Result<(), UpdateError> {
     // Make sure we received a new update
-    // TODO: do not drop error here
     let update = update.map_err(UpdateError::Telegram)?;
 
     // Process messages
@@ -94,19 +207,33 @@ async fn handle_private(state: &State, msg: &Message) -> Result<(), ()> {
 
     // Test message for legality, and build legality text
     let timer = Timer::new();
-    let illegal = is_illegal_message(msg.clone(), state.clone()).await;
+    let verdict = is_illegal_message(msg.clone(), state.clone()).await;
     let took = timer.took();
-    let legality_text = if illegal {
-        format!("_Unsafe! Your message is considered unsafe as it seems to contain Binance spam!\nThe message would be deleted automatically by this bot in groups the bot is added in._")
-    } else {
-        format!("_Safe. Your message is considered safe, and is not seen as Binance spam.\nSend me something else to test._")
+    let config = state.chat_config(msg.chat.id());
+    let scoring = &config.scanner.scoring;
+    let score = verdict.as_ref().map_or(0.0, |verdict| scoring.score(verdict));
+    let legality_text = match (&verdict, scoring.action(verdict.as_ref())) {
+        (Some(verdict), Action::Ignore) => format!(
+            "_Safe. Your message matched some rules, but not enough to be seen as Binance spam.\nSend me something else to test._\n\n*Score:* {:.2}\n*Matched:*\n{}",
+            score,
+            format_verdict(verdict),
+        ),
+        (Some(verdict), action) => format!(
+            "_Unsafe! Your message is considered unsafe as it seems to contain Binance spam!\nThe message would be {} automatically by this bot in groups the bot is added in._\n\n*Score:* {:.2}\n*Reason:*\n{}",
+            action_text(action),
+            score,
+            format_verdict(verdict),
+        ),
+        (None, _) => format!("_Safe. Your message is considered safe, and is not seen as Binance spam.\nSend me something else to test._"),
     };
 
-    if illegal {
+    if let Some(verdict) = &verdict {
         warn!(
-            "Direct message from {} audits as unsafe (audit took {})",
+            "Direct message from {} audits as unsafe (score: {:.2}, audit took {}): {}",
             util::telegram::format_user_name_log(&msg.from),
+            score,
             took,
+            verdict,
         );
     }
 
@@ -129,43 +256,82 @@ async fn handle_private(state: &State, msg: &Message) -> Result<(), ()> {
 
 /// Handle the given message.
 ///
-/// This checks if the message is illegal, and immediately bans the sender if it is.
+/// This checks if the message is illegal, and immediately takes the action matching its score.
+/// The sender is banned if the ban threshold is reached.
 async fn handle_message(msg: Message, state: State) -> Result<(), ()> {
     // Log added/removed to group/channel messages
     if log_added_removed(&msg, &state) {
         return Ok(());
     }
 
-    // Return if not illegal, ban user otherwise
+    // Handle trusted commands right away, they may quote spam such as a URL to uncache
+    let command = match Command::parse(&state, &msg).await {
+        Some(command) if command.is_trusted() => {
+            commands::handle(&state, &msg, command).await;
+            return Ok(());
+        }
+        command => command,
+    };
+
+    // Return if not illegal, determine what action to take otherwise
     let timer = Timer::new();
-    if !is_illegal_message(msg.clone(), state.clone()).await {
-        return Ok(());
-    }
+    let verdict = is_illegal_message(msg.clone(), state.clone()).await;
     let took = timer.took();
+    let config = state.chat_config(msg.chat.id());
+    let scoring = &config.scanner.scoring;
+    let action = scoring.action(verdict.as_ref());
+    let (verdict, action) = match (verdict, action) {
+        (Some(verdict), action) if action != Action::Ignore => (verdict, action),
+        _ => {
+            // Handle legal messages as bot commands
+            if let Some(command) = command {
+                commands::handle(&state, &msg, command).await;
+            }
+            return Ok(());
+        }
+    };
+    let score = scoring.score(&verdict);
 
     info!(
-        "Banning {} in {} for spam (audit took {})",
+        "Taking action {:?} on {} in {} for spam (score: {:.2}, audit took {}): {}",
+        action,
         util::telegram::format_user_name_log(&msg.from),
         util::telegram::format_chat_name_log(&msg.chat),
+        score,
         took,
+        verdict,
     );
 
     // Build the message, keep a reference to the chat
     let name = util::telegram::format_user_name(&msg.from);
     let chat = &msg.chat;
 
-    // Attempt to kick the user, and delete their message
-    let kick_user = state
-        .telegram_client()
-        .send(msg.from.kick_from(&chat))
-        .await;
+    // Attempt to ban or restrict the user
+    let punish_user = match action {
+        Action::Ban => Some(state.telegram_client().send(msg.from.kick_from(&chat)).await),
+        Action::Restrict => Some(
+            state
+                .telegram_client()
+                .send(RestrictChatMember::mute(chat.id(), msg.from.id))
+                .await,
+        ),
+        _ => None,
+    };
+    let punish_failed = punish_user.as_ref().map_or(false, |result| result.is_err());
+
+    // Archive banned text, to recognize reworded copies
+    if action == Action::Ban {
+        if let Some(text) = msg.text() {
+            let words = util::text::words(&text);
+            if let Err(err) = state.archive().insert(&config.archive, &words) {
+                warn!("Failed to archive banned spam text: {:?}", err);
+            }
+        }
+    }
 
     // Forward the message to the global spam log chat
     let mut forward_msg = None;
-    let forward_chat_id = env::var("GLOBAL_SPAM_LOG_CHAT_ID")
-        .ok()
-        .and_then(|id| id.parse().ok());
-    if let Some(id) = forward_chat_id {
+    if let Some(id) = state.config().general.spam_log_chat {
         // Do not forward if in same chat
         let id = ChatId::new(id);
         if msg.chat.id() == id {
@@ -179,6 +345,14 @@ async fn handle_message(msg: Message, state: State) -> Result<(), ()> {
         }
     }
 
+    // Only flag the message, if below the delete threshold
+    if action == Action::Flag {
+        if let Some(forward_msg) = forward_msg {
+            annotate_forward(&state, &msg, forward_msg, action, score, &verdict, took);
+        }
+        return Ok(());
+    }
+
     // Delete the user message
     let delete = state.telegram_client().send(msg.delete()).await;
     if let Err(err) = &delete {
@@ -189,11 +363,12 @@ async fn handle_message(msg: Message, state: State) -> Result<(), ()> {
     }
 
     // Build the notification to share in the chat
-    let mut notification = if kick_user.is_err() {
+    let mut notification = if punish_failed {
         format!(
-            "An admin should ban {} for posting spam/phishing.{}\n\n\
+            "An admin should {} {} for posting spam/phishing.{}\n\n\
             [Add](https://github.com/timvisee/ban-binance-bot/blob/master/README.md#how-to-use) this bot as explicit administrator to automatically ban users posting new promotions. \
             Administrators are never banned.",
+            if action == Action::Ban { "ban" } else { "restrict" },
             name,
             if delete.is_ok() {
                 " I've deleted the message."
@@ -202,73 +377,61 @@ async fn handle_message(msg: Message, state: State) -> Result<(), ()> {
             }
         )
     } else {
-        format!("Automatically banned {} for posting spam/phishing.", name,)
+        match action {
+            Action::Ban => format!("Automatically banned {} for posting spam/phishing.", name),
+            Action::Restrict => format!(
+                "Automatically restricted {} from sending messages for posting spam/phishing.",
+                name,
+            ),
+            _ if delete.is_ok() => {
+                format!("Deleted a message from {} that looks like spam/phishing.", name)
+            }
+            _ => format!(
+                "An admin should delete a message from {} that looks like spam/phishing.",
+                name,
+            ),
+        }
     };
 
     // Add self-destruct notice
-    let self_destruct = NOTIFY_SELF_DESTRUCT_TIME.is_some();
-    if self_destruct {
+    let self_destruct = config.general.notification_self_destruct;
+    if self_destruct > 0 {
         notification += &format!(
             "\n\n_This message will self-destruct in {} seconds..._",
-            NOTIFY_SELF_DESTRUCT_TIME.unwrap()
+            self_destruct,
         );
     }
 
-    // Attempt to send a ban notification to the chat
-    let notify_msg = state
-        .telegram_client()
-        .send(
-            // TODO: only show self destruct if actually self destructing
-            // TODO: make time configurable
-            chat.text(notification)
-                .parse_mode(ParseMode::Markdown)
-                .disable_preview()
-                .disable_notification(),
-        )
-        .inspect_err(|err| warn!("Failed to post ban notification in chat: {:?}", err))
-        .await;
+    // Attempt to send a ban notification to the chat, skip if disabled and no admin is needed
+    let notify = config.general.notify || punish_failed || delete.is_err();
+    let notify_msg = if notify {
+        state
+            .telegram_client()
+            .send(
+                // TODO: only show self destruct if actually self destructing
+                chat.text(notification)
+                    .parse_mode(ParseMode::Markdown)
+                    .disable_preview()
+                    .disable_notification(),
+            )
+            .inspect_err(|err| warn!("Failed to post ban notification in chat: {:?}", err))
+            .await
+            .ok()
+    } else {
+        None
+    };
 
     // Annotate forwarded spam message
     if let Some(forward_msg) = forward_msg {
-        // Link 'this' word to specific banned message if possible
-        let msg_this_link = match msg.chat.username_link() {
-            Some(link) => format!("[this]({}/{})", link, msg.id),
-            None => "this".into(),
-        };
-
-        // Format forward annotation message
-        let state = state.clone();
-        let mut annotate = forward_msg.text_reply(format!(
-            "Banned {} message from {} in {}.\n\n_Audit took {}._",
-            msg_this_link,
-            util::telegram::format_user_name(&msg.from),
-            util::telegram::format_chat_name(&msg.chat),
-            took,
-        ));
-
-        tokio::spawn(async move {
-            // Wait, prevent throttling, then annotate the forwarded spam
-            delay_for(Duration::from_secs(2)).await;
-            state
-                .telegram_client()
-                .send(
-                    annotate
-                        .parse_mode(ParseMode::Markdown)
-                        .disable_preview()
-                        .disable_notification(),
-                )
-                .inspect_err(|err| warn!("Failed to annotate forwarded spam message: {:?}", err))
-                .map(|_| ())
-                .await
-        });
+        annotate_forward(&state, &msg, forward_msg, action, score, &verdict, took);
     }
 
     // Self-destruct messages
-    if self_destruct {
-        if let Ok(msg) = notify_msg {
-            tokio::spawn(async move {
-                // Wait, then self destruct the message
-                delay_for(Duration::from_secs(NOTIFY_SELF_DESTRUCT_TIME.unwrap())).await;
+    if self_destruct > 0 {
+        if let Some(msg) = notify_msg {
+            // Wait, then self destruct the message
+            let scheduler = state.scheduler().clone();
+            scheduler.schedule(Duration::from_secs(self_destruct), async move {
                 state
                     .telegram_client()
                     .send(msg.delete())
@@ -282,6 +445,59 @@ async fn handle_message(msg: Message, state: State) -> Result<(), ()> {
     Ok(())
 }
 
+/// Annotate the given message forwarded to the spam log chat, with the action taken and why.
+///
+/// The annotation is scheduled to be sent a bit later, to prevent throttling.
+fn annotate_forward(
+    state: &State,
+    msg: &Message,
+    forward_msg: Message,
+    action: Action,
+    score: f64,
+    verdict: &Verdict,
+    took: Took,
+) {
+    // Link 'this' word to specific message if possible
+    let msg_this_link = match msg.chat.username_link() {
+        Some(link) => format!("[this]({}/{})", link, msg.id),
+        None => "this".into(),
+    };
+
+    // Format forward annotation message
+    let mut annotate = forward_msg.text_reply(format!(
+        "{} {} message from {} in {}.\n\n*Score:* {:.2}\n*Reason:*\n{}\n\n_Audit took {}._",
+        match action {
+            Action::Ban => "Banned",
+            Action::Restrict => "Restricted",
+            Action::Delete => "Deleted",
+            Action::Flag | Action::Ignore => "Flagged",
+        },
+        msg_this_link,
+        util::telegram::format_user_name(&msg.from),
+        util::telegram::format_chat_name(&msg.chat),
+        score,
+        format_verdict(verdict),
+        took,
+    ));
+
+    // Wait, prevent throttling, then annotate the forwarded spam
+    let state = state.clone();
+    let scheduler = state.scheduler().clone();
+    scheduler.schedule(Duration::from_secs(2), async move {
+        state
+            .telegram_client()
+            .send(
+                annotate
+                    .parse_mode(ParseMode::Markdown)
+                    .disable_preview()
+                    .disable_notification(),
+            )
+            .inspect_err(|err| warn!("Failed to annotate forwarded spam message: {:?}", err))
+            .map(|_| ())
+            .await
+    });
+}
+
 /// Report to log whether this bot is added/removed from groups/channels.
 ///
 /// If the given message represents that this bot has been added or removed from a group or channel,
@@ -313,11 +529,40 @@ fn log_added_removed(msg: &Message, state: &State) -> bool {
     false
 }
 
+/// Describe what happens to a message that gets the given action, such as "deleted".
+fn action_text(action: Action) -> &'static str {
+    match action {
+        Action::Ban => "deleted and its sender banned",
+        Action::Restrict => "deleted and its sender restricted",
+        Action::Delete => "deleted",
+        Action::Flag => "flagged for review",
+        Action::Ignore => "ignored",
+    }
+}
+
+/// Format the given verdict to show in a Telegram message.
+///
+/// The returned string should be sent with `.parse_mode(ParseMode::Markdown)` enabled.
+fn format_verdict(verdict: &Verdict) -> String {
+    format!("`{}`", verdict.to_string().replace('`', "'"))
+}
+
 /// Check whether the given message is illegal.
-async fn is_illegal_message(msg: Message, state: State) -> bool {
+///
+/// Returns a verdict describing why the message is illegal, or `None` if it is considered safe.
+/// The score of the verdict determines what action to take, see `Scoring::action`.
+async fn is_illegal_message(msg: Message, state: State) -> Option<Verdict> {
+    let config = state.chat_config(msg.chat.id());
+    let config = config.as_ref();
     let mut checks = vec![];
 
-    // Check message text
+    // Only remember fingerprints of group messages, users may test anything in private
+    let record = match msg.chat {
+        MessageChat::Private(..) => false,
+        _ => true,
+    };
+
+    // Check message text and hidden URLs
     if let Some(text) = msg.text() {
         trace!(
             "TEXT {}/{}: {}",
@@ -326,21 +571,74 @@ async fn is_illegal_message(msg: Message, state: State) -> bool {
             text,
         );
 
-        // Scan any hidden URLs
-        match &msg.kind {
-            MessageKind::Text { entities, .. } => {
-                let urls = util::url::find_hidden_urls(entities);
-                if !urls.is_empty() {
-                    checks.push(
-                        scanner::url::any_illegal_url(&state.config().scanner.web, urls, 0).boxed(),
-                    );
+        let hidden_urls = match &msg.kind {
+            MessageKind::Text { entities, .. } => util::url::find_hidden_urls(entities),
+            _ => vec![],
+        };
+
+        // Fingerprint long texts, to recognize them when posted again in any chat
+        let length = skeleton(&text).chars().count();
+        let fingerprint = if length >= config.fingerprints.min_text_length {
+            Some(util::text::fingerprint(&text))
+        } else {
+            None
+        };
+        if let Some(fingerprint) = fingerprint {
+            let known = state
+                .fingerprints()
+                .find(&config.fingerprints, &Fingerprint::Text(fingerprint));
+            if let Some(verdict) = known {
+                debug!("Message text matches fingerprint of illegal content");
+                return Some(verdict);
+            }
+
+            // Raise a flood signal if posted in many chats
+            if record {
+                let chats = state.fingerprints().see_text(
+                    &config.fingerprints,
+                    fingerprint,
+                    i64::from(msg.chat.id()),
+                );
+                if config.fingerprints.flood_chats > 0 && chats >= config.fingerprints.flood_chats {
+                    let verdict = Verdict::new(Kind::Text, Rule::Flood { chats });
+                    checks.push(future::ready(Some(verdict)).boxed());
                 }
             }
-            _ => {}
         }
 
-        // Scan the regular text
-        checks.push(scanner::text::is_illegal_text(&state.config().scanner, text).boxed());
+        // Check for near duplicates of banned spam
+        let words = util::text::words(&text);
+        if let Some(distance) = state.archive().find(&config.archive, &words) {
+            let verdict = Verdict::new(Kind::Text, Rule::NearDuplicate { distance });
+            checks.push(future::ready(Some(verdict)).boxed());
+        }
+
+        let input = scanner::message::Message {
+            text: Some(text),
+            hidden_urls,
+            files: vec![],
+        };
+        let fingerprints = state.fingerprints().clone();
+        let context = state.context();
+        checks.push(
+            async move {
+                let verdict =
+                    scanner::message::is_illegal_message(&config.scanner, &context, &input).await;
+
+                // Remember the fingerprint of illegal text
+                if let (true, Some(fingerprint), Some(verdict)) = (record, fingerprint, &verdict) {
+                    if config.scanner.scoring.action(Some(verdict)) >= Action::Delete {
+                        fingerprints.insert(
+                            &config.fingerprints,
+                            Fingerprint::Text(fingerprint),
+                            verdict,
+                        );
+                    }
+                }
+                verdict
+            }
+            .boxed(),
+        );
     }
 
     // Check message files (pictures, stickers, files, ...)
@@ -354,11 +652,47 @@ async fn is_illegal_message(msg: Message, state: State) -> bool {
 
         // TODO: do not clone state here
         checks.push(
-            scanner::file::has_illegal_files(&state.config().scanner, files, state.clone()).boxed(),
+            files::has_illegal_files(config, files, record, state.clone()).boxed(),
         );
     }
 
-    select_true(checks).await
+    select_verdicts(&config.scanner.scoring, checks).await
+}
+
+/// Telegram API request to restrict a chat member.
+#[derive(Serialize, Debug)]
+struct RestrictChatMember {
+    chat_id: ChatId,
+    user_id: UserId,
+    permissions: ChatPermissions,
+}
+
+impl RestrictChatMember {
+    /// Restrict the given user from sending messages in the given chat.
+    fn mute(chat_id: ChatId, user_id: UserId) -> Self {
+        Self {
+            chat_id,
+            user_id,
+            permissions: ChatPermissions {
+                can_send_messages: false,
+            },
+        }
+    }
+}
+
+impl Request for RestrictChatMember {
+    type Type = JsonRequestType<Self>;
+    type Response = JsonTrueToUnitResponse;
+
+    fn serialize(&self) -> Result<TelegramRequest, RawError> {
+        Self::Type::serialize(RequestUrl::method("restrictChatMember"), self)
+    }
+}
+
+/// Chat member permissions, for `RestrictChatMember`.
+#[derive(Serialize, Debug)]
+struct ChatPermissions {
+    can_send_messages: bool,
 }
 
 /// The update error kind.
@@ -367,6 +701,9 @@ pub enum UpdateError {
     /// An error occurred in the Telegram API.
     Telegram(TelegramError),
 
+    /// Failed to set up the webhook listener.
+    Webhook(webhook::Error),
+
     /// An other update occurred.
     Other,
 }
@@ -376,3 +713,6 @@ impl From<()> for UpdateError {
         UpdateError::Other
     }
 }
+
+#[cfg(test)]
+mod tests;
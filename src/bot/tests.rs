@@ -0,0 +1,639 @@
+//! End-to-end tests for the moderation flow, against a fake Telegram Bot API.
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::fs;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use ban_binance_bot::{
+    config::{pattern::Pattern, Config, Phrase},
+    scanner::verdict::{Kind, Rule, Verdict},
+};
+use futures::{channel::oneshot, future, prelude::*};
+use serde_json::{json, Value};
+use tempfile::NamedTempFile;
+use telegram_bot::{
+    connector::Connector,
+    types::{Body, ChatId, HttpRequest, HttpResponse, Update},
+    Api, Error as TelegramError,
+};
+use tokio::timer::delay_for;
+use url::Url;
+
+use super::{handle_update, run_telegram_handler};
+use crate::{reload::reload, state::State};
+
+/// Fixture configuration to run the bot with.
+const CONFIG: &str = "./tests/fixtures/config.toml";
+
+/// Chat ID of the group chat messages are posted in.
+const CHAT_ID: i64 = -1001;
+
+/// Chat ID of the spam log chat.
+const SPAM_LOG_CHAT_ID: i64 = -1002;
+
+/// User ID of the bot itself.
+const BOT_ID: i64 = 1;
+
+/// User ID of the user posting messages.
+const USER_ID: i64 = 2;
+
+/// A fake Telegram Bot API.
+///
+/// This records all requests made, and responds with minimal valid responses.
+#[derive(Clone, Default)]
+struct FakeApi {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// All requests made, as method name and JSON parameters.
+    calls: Vec<(String, Value)>,
+
+    /// Queued updates to return on `getUpdates`.
+    updates: VecDeque<Value>,
+
+    /// Methods that should fail.
+    failing: HashSet<&'static str>,
+
+    /// The last message ID that was used.
+    message_id: i64,
+}
+
+impl FakeApi {
+    /// Queue the given update, to return on the next `getUpdates`.
+    fn queue(&self, update: Value) {
+        self.inner.lock().unwrap().updates.push_back(update);
+    }
+
+    /// Make all requests with the given method fail.
+    fn fail(&self, method: &'static str) {
+        self.inner.lock().unwrap().failing.insert(method);
+    }
+
+    /// Get the parameters of all requests made with the given method.
+    fn calls(&self, method: &str) -> Vec<Value> {
+        self.inner
+            .lock()
+            .unwrap()
+            .calls
+            .iter()
+            .filter(|(name, _)| name == method)
+            .map(|(_, params)| params.clone())
+            .collect()
+    }
+
+    /// Respond to the given method call.
+    fn respond(&self, method: &str, params: &Value) -> Result<Value, String> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.calls.push((method.into(), params.clone()));
+
+        if inner.failing.contains(method) {
+            return Err("Bad Request: not enough rights".into());
+        }
+
+        match method {
+            "getMe" => Ok(json!({
+                "id": BOT_ID,
+                "is_bot": true,
+                "first_name": "Ban Binance Bot",
+                "username": "banbinancebot",
+            })),
+            "getUpdates" => Ok(Value::Array(inner.updates.drain(..).collect())),
+            "getChatMember" => Ok(json!({
+                "user": { "id": USER_ID, "is_bot": false, "first_name": "Admin" },
+                "status": "administrator",
+            })),
+            "kickChatMember" | "restrictChatMember" | "deleteMessage" | "setWebhook"
+            | "deleteWebhook" => Ok(json!(true)),
+            "sendMessage" | "forwardMessage" | "editMessageText" => {
+                inner.message_id += 1;
+                let chat_id = params["chat_id"].as_i64().unwrap_or(CHAT_ID);
+                let text = params["text"].as_str().unwrap_or("forwarded");
+                Ok(bot_message(inner.message_id + 1000, chat_id, text))
+            }
+            method => Err(format!("Not Found: method {} not supported", method)),
+        }
+    }
+}
+
+impl fmt::Debug for FakeApi {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "FakeApi")
+    }
+}
+
+impl Connector for FakeApi {
+    fn request(
+        &self,
+        _token: &str,
+        req: HttpRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<HttpResponse, TelegramError>> + Send>> {
+        let params = match &req.body {
+            Body::Json(body) => serde_json::from_str(body).unwrap_or(Value::Null),
+            _ => Value::Null,
+        };
+
+        // Like long polling, hold back empty updates for a moment
+        let poll = req.name() == "getUpdates" && self.inner.lock().unwrap().updates.is_empty();
+
+        let response = match self.respond(req.name(), &params) {
+            Ok(result) => json!({ "ok": true, "result": result }),
+            Err(description) => json!({ "ok": false, "description": description }),
+        };
+        let body = serde_json::to_vec(&response).expect("failed to serialize fake response");
+        let response = HttpResponse { body: Some(body) };
+        if poll {
+            let delay = delay_for(Duration::from_millis(10));
+            Box::pin(delay.map(move |_| Ok::<_, TelegramError>(response)))
+        } else {
+            Box::pin(future::ok(response))
+        }
+    }
+}
+
+/// Build a message sent by the bot in the given chat.
+fn bot_message(id: i64, chat_id: i64, text: &str) -> Value {
+    json!({
+        "message_id": id,
+        "from": { "id": BOT_ID, "is_bot": true, "first_name": "Ban Binance Bot" },
+        "date": 0,
+        "chat": { "id": chat_id, "type": "supergroup", "title": "Chat" },
+        "text": text,
+    })
+}
+
+/// Build an update for a user posting the given text in the group chat.
+fn text_update(id: i64, text: &str) -> Update {
+    text_update_in(id, CHAT_ID, text)
+}
+
+/// Build an update for a text message sent in the given group chat.
+fn text_update_in(id: i64, chat_id: i64, text: &str) -> Update {
+    serde_json::from_value(text_update_json(id, chat_id, text)).expect("failed to build update")
+}
+
+/// Build the raw JSON update for a text message sent in the given group chat.
+fn text_update_json(id: i64, chat_id: i64, text: &str) -> Value {
+    json!({
+        "update_id": id,
+        "message": {
+            "message_id": id,
+            "from": { "id": USER_ID, "is_bot": false, "first_name": "Spammer" },
+            "date": 0,
+            "chat": { "id": chat_id, "type": "supergroup", "title": "Group" },
+            "text": text,
+        },
+    })
+}
+
+/// Load the fixture configuration.
+fn config() -> Config {
+    Config::from_path(CONFIG).expect("failed to load fixture config")
+}
+
+/// Build the bot state running against the given fake API.
+async fn state(api: &FakeApi) -> State {
+    state_with(api, config()).await
+}
+
+/// Build the bot state running against the given fake API, with the given configuration.
+async fn state_with(api: &FakeApi, config: Config) -> State {
+    let client = Api::with_connector("TOKEN", Box::new(api.clone()));
+    State::new("TOKEN".into(), Arc::new(config), client)
+        .await
+        .expect("failed to initialize state")
+}
+
+#[tokio::test]
+async fn bans_spam() {
+    let api = FakeApi::default();
+    let state = state(&api).await;
+
+    handle_update(state, Ok(text_update(1, "Only the first 5000 users will be rewarded")))
+        .await
+        .expect("failed to handle update");
+
+    let kicks = api.calls("kickChatMember");
+    assert_eq!(kicks.len(), 1);
+    assert_eq!(kicks[0]["chat_id"], CHAT_ID);
+    assert_eq!(kicks[0]["user_id"], USER_ID);
+
+    let deletes = api.calls("deleteMessage");
+    assert_eq!(deletes.len(), 1);
+    assert_eq!(deletes[0]["message_id"], 1);
+
+    let notifications = api.calls("sendMessage");
+    assert_eq!(notifications.len(), 1);
+    assert!(notifications[0]["text"]
+        .as_str()
+        .unwrap()
+        .starts_with("Automatically banned Spammer"));
+}
+
+#[tokio::test]
+async fn polls_updates_until_shutdown() {
+    let api = FakeApi::default();
+    let state = state(&api).await;
+    api.queue(text_update_json(1, CHAT_ID, "Only the first 5000 users will be rewarded"));
+    api.queue(text_update_json(2, CHAT_ID, "Good morning everyone!"));
+
+    // Shut down once the spam is handled
+    let (stop, stopped) = oneshot::channel::<()>();
+    let driver = {
+        let api = api.clone();
+        async move {
+            for _ in 0..500 {
+                if !api.calls("kickChatMember").is_empty() {
+                    break;
+                }
+                delay_for(Duration::from_millis(10)).await;
+            }
+            let _ = stop.send(());
+        }
+    };
+    let (result, ()) =
+        future::join(run_telegram_handler(state, stopped.map(|_| ())), driver).await;
+    result.expect("failed to run update handler");
+
+    // A webhook must not be registered while polling
+    assert_eq!(api.calls("deleteWebhook").len(), 1);
+    assert!(!api.calls("getUpdates").is_empty());
+    let kicks = api.calls("kickChatMember");
+    assert_eq!(kicks.len(), 1);
+    assert_eq!(kicks[0]["user_id"], USER_ID);
+    assert_eq!(api.calls("deleteMessage").len(), 1);
+}
+
+#[tokio::test]
+async fn ignores_legal_message() {
+    let api = FakeApi::default();
+    let state = state(&api).await;
+
+    handle_update(state, Ok(text_update(1, "Good morning everyone!")))
+        .await
+        .expect("failed to handle update");
+
+    assert!(api.calls("kickChatMember").is_empty());
+    assert!(api.calls("deleteMessage").is_empty());
+    assert!(api.calls("sendMessage").is_empty());
+}
+
+#[tokio::test]
+async fn failed_kick_asks_admin() {
+    let api = FakeApi::default();
+    api.fail("kickChatMember");
+    let state = state(&api).await;
+
+    handle_update(state, Ok(text_update(1, "Celebrating Our New Crypto Exchange")))
+        .await
+        .expect("failed to handle update");
+
+    assert_eq!(api.calls("deleteMessage").len(), 1);
+    let notification = api.calls("sendMessage").remove(0);
+    let text = notification["text"].as_str().unwrap();
+    assert!(text.starts_with("An admin should ban Spammer"));
+    assert!(text.contains("I've deleted the message."));
+}
+
+#[tokio::test]
+async fn failed_delete_is_reported() {
+    let api = FakeApi::default();
+    api.fail("kickChatMember");
+    api.fail("deleteMessage");
+    let state = state(&api).await;
+
+    handle_update(state, Ok(text_update(1, "Celebrating Our New Crypto Exchange")))
+        .await
+        .expect("failed to handle update");
+
+    let notification = api.calls("sendMessage").remove(0);
+    let text = notification["text"].as_str().unwrap();
+    assert!(text.starts_with("An admin should ban Spammer"));
+    assert!(!text.contains("I've deleted the message."));
+}
+
+#[tokio::test]
+async fn forwards_to_spam_log() {
+    let mut config = config();
+    config.general.spam_log_chat = Some(SPAM_LOG_CHAT_ID);
+    let api = FakeApi::default();
+    let state = state_with(&api, config).await;
+
+    handle_update(state.clone(), Ok(text_update(1, "https://binance.jerseyonline.icu/event")))
+        .await
+        .expect("failed to handle update");
+
+    let forwards = api.calls("forwardMessage");
+    assert_eq!(forwards.len(), 1);
+    assert_eq!(forwards[0]["chat_id"], SPAM_LOG_CHAT_ID);
+    assert_eq!(forwards[0]["from_chat_id"], CHAT_ID);
+
+    // The forwarded message is annotated after a short delay, run it now
+    state.scheduler().flush(Duration::from_secs(5)).await;
+    let annotations: Vec<_> = api
+        .calls("sendMessage")
+        .into_iter()
+        .filter(|params| params["chat_id"] == SPAM_LOG_CHAT_ID)
+        .collect();
+    assert_eq!(annotations.len(), 1);
+    let text = annotations[0]["text"].as_str().unwrap();
+    assert!(text.contains("binance.jerseyonline.icu"));
+}
+
+#[tokio::test]
+async fn deletes_below_ban_threshold() {
+    let mut config = config();
+    config.scanner.scoring.thresholds.delete = Some(0.5);
+    config.scanner.scoring.phrases.push(Phrase {
+        rule: Pattern::literal("Event ends today!"),
+        weight: 0.5,
+    });
+    config.scanner.compile();
+    let api = FakeApi::default();
+    let state = state_with(&api, config).await;
+
+    handle_update(state, Ok(text_update(1, "Event ends today!")))
+        .await
+        .expect("failed to handle update");
+
+    assert!(api.calls("kickChatMember").is_empty());
+    assert_eq!(api.calls("deleteMessage").len(), 1);
+    let notification = api.calls("sendMessage").remove(0);
+    assert!(notification["text"]
+        .as_str()
+        .unwrap()
+        .starts_with("Deleted a message from Spammer"));
+}
+
+#[tokio::test]
+async fn restricts_below_ban_threshold() {
+    let mut config = config();
+    config.scanner.scoring.weights.text = 0.8;
+    config.scanner.scoring.thresholds.restrict = Some(0.8);
+    let api = FakeApi::default();
+    let state = state_with(&api, config).await;
+
+    handle_update(state, Ok(text_update(1, "First 5000 Participants Bonus")))
+        .await
+        .expect("failed to handle update");
+
+    assert!(api.calls("kickChatMember").is_empty());
+    let restricts = api.calls("restrictChatMember");
+    assert_eq!(restricts.len(), 1);
+    assert_eq!(restricts[0]["user_id"], USER_ID);
+    assert_eq!(restricts[0]["permissions"]["can_send_messages"], false);
+    assert_eq!(api.calls("deleteMessage").len(), 1);
+}
+
+#[tokio::test]
+async fn bans_text_flooding_chats() {
+    let mut config = config();
+    config.scanner.scoring.weights.flood = 1.0;
+    let api = FakeApi::default();
+    let state = state_with(&api, config).await;
+
+    let text = "Hello everyone, please have a look at my new channel";
+    for (id, chat_id) in [(1, -1001), (2, -1002)].iter() {
+        handle_update(state.clone(), Ok(text_update_in(*id, *chat_id, text)))
+            .await
+            .expect("failed to handle update");
+    }
+    assert!(api.calls("kickChatMember").is_empty());
+
+    handle_update(state, Ok(text_update_in(3, -1003, text)))
+        .await
+        .expect("failed to handle update");
+
+    let kicks = api.calls("kickChatMember");
+    assert_eq!(kicks.len(), 1);
+    assert_eq!(kicks[0]["chat_id"], -1003);
+}
+
+#[tokio::test]
+async fn deletes_near_duplicate_of_banned_spam() {
+    let mut config = config();
+    config.scanner.scoring.thresholds.delete = Some(0.5);
+    let api = FakeApi::default();
+    let state = state_with(&api, config).await;
+
+    let spam = "To celebrate the launch of Binance US we send a deposit bonus to everyone, only the \
+        first 5000 users will be rewarded so join the event now";
+    handle_update(state.clone(), Ok(text_update(1, spam)))
+        .await
+        .expect("failed to handle update");
+    assert_eq!(api.calls("kickChatMember").len(), 1);
+
+    // Reworded to bypass all text rules
+    let reworded = "To mark the launch of Binance US we send a deposit bonus to everyone, only the \
+        first 5000 users will get rewarded so join the event now";
+    handle_update(state, Ok(text_update(2, reworded)))
+        .await
+        .expect("failed to handle update");
+    assert_eq!(api.calls("deleteMessage").len(), 2);
+
+    // A near duplicate alone is not enough to ban
+    assert_eq!(api.calls("kickChatMember").len(), 1);
+}
+
+#[tokio::test]
+async fn notification_self_destructs() {
+    let mut config = config();
+    config.general.notification_self_destruct = 1;
+    let api = FakeApi::default();
+    let state = state_with(&api, config).await;
+
+    handle_update(state.clone(), Ok(text_update(1, "First 5000 Participants Bonus")))
+        .await
+        .expect("failed to handle update");
+
+    let notification = api.calls("sendMessage").remove(0);
+    assert!(notification["text"]
+        .as_str()
+        .unwrap()
+        .contains("This message will self-destruct in 1 seconds"));
+
+    // Only the spam message is deleted until the notification self-destructs
+    assert_eq!(api.calls("deleteMessage").len(), 1);
+    state.scheduler().flush(Duration::from_secs(5)).await;
+    let deletes = api.calls("deleteMessage");
+    assert_eq!(deletes.len(), 2);
+    assert_eq!(deletes[1]["message_id"], 1001);
+}
+
+#[tokio::test]
+async fn notification_self_destruct_disabled() {
+    let mut config = config();
+    config.general.notification_self_destruct = 0;
+    let api = FakeApi::default();
+    let state = state_with(&api, config).await;
+
+    handle_update(state, Ok(text_update(1, "First 5000 Participants Bonus")))
+        .await
+        .expect("failed to handle update");
+
+    let notification = api.calls("sendMessage").remove(0);
+    assert!(!notification["text"].as_str().unwrap().contains("self-destruct"));
+}
+
+#[tokio::test]
+async fn reload_rejects_invalid_config() {
+    let api = FakeApi::default();
+    let state = state(&api).await;
+    let fixture = fs::read_to_string(CONFIG).expect("failed to read fixture config");
+    let file = NamedTempFile::new().expect("failed to create temporary file");
+    let path = file.path().to_str().unwrap();
+
+    // An invalid configuration is rejected, the current configuration is kept
+    fs::write(path, fixture.replace("threshold = 0.5", "threshold = 5.0")).unwrap();
+    assert!(reload(&state, path).is_err());
+    assert_eq!(state.config().scanner.image.threshold, 0.5);
+
+    // A valid configuration is swapped in, and used for new audits
+    let needle = "\"First 5000 Participants Bonus\",";
+    fs::write(path, fixture.replacen(needle, &format!("{}\n    \"A brand new spam wave\",", needle), 1)).unwrap();
+    reload(&state, path).expect("failed to reload valid config");
+
+    handle_update(state, Ok(text_update(1, "Join a brand new spam wave")))
+        .await
+        .expect("failed to handle update");
+    assert_eq!(api.calls("kickChatMember").len(), 1);
+}
+
+#[tokio::test]
+async fn reload_rebuilds_url_cache() {
+    let api = FakeApi::default();
+    let state = state(&api).await;
+    let url = Url::parse("https://bit.ly/spam").unwrap();
+    let revision = state.config().scanner.web.revision;
+    state.context().urls.insert(&url, Url::parse("https://example.com/").unwrap(), None, revision);
+
+    // The cache is kept if its configuration is unchanged
+    state.set_config(Config::clone(&state.config()));
+    assert!(state.context().urls.get(&url).is_some());
+
+    // A changed configuration rebuilds the cache
+    let mut config = Config::clone(&state.config());
+    config.url_cache.ttl = 60;
+    state.set_config(config);
+    assert!(state.context().urls.get(&url).is_none());
+}
+
+#[tokio::test]
+async fn admin_disables_notifications() {
+    let api = FakeApi::default();
+    let state = state(&api).await;
+
+    handle_update(state.clone(), Ok(text_update(1, "/set notify false")))
+        .await
+        .expect("failed to handle update");
+    let replies = api.calls("sendMessage");
+    assert_eq!(replies.len(), 1);
+    assert!(replies[0]["text"].as_str().unwrap().contains("notify: false"));
+
+    // Spam is now removed silently in this chat
+    handle_update(state, Ok(text_update(2, "First 5000 Participants Bonus")))
+        .await
+        .expect("failed to handle update");
+    assert_eq!(api.calls("kickChatMember").len(), 1);
+    assert_eq!(api.calls("deleteMessage").len(), 1);
+    assert_eq!(api.calls("sendMessage").len(), 1);
+}
+
+#[tokio::test]
+async fn non_admin_cannot_change_settings() {
+    let api = FakeApi::default();
+    api.fail("getChatMember");
+    let state = state(&api).await;
+
+    handle_update(state.clone(), Ok(text_update(1, "/set notify false")))
+        .await
+        .expect("failed to handle update");
+    let replies = api.calls("sendMessage");
+    assert_eq!(replies.len(), 1);
+    assert!(replies[0]["text"].as_str().unwrap().contains("Only group administrators"));
+    assert!(state.chats().get(ChatId::new(CHAT_ID)).is_empty());
+}
+
+/// Build the bot state running against the given fake API, with the sender as bot operator.
+async fn operator_state(api: &FakeApi) -> State {
+    let mut config = config();
+    config.general.operators = vec![USER_ID];
+    state_with(api, config).await
+}
+
+#[tokio::test]
+async fn operator_invalidates_cached_url() {
+    let api = FakeApi::default();
+    let state = operator_state(&api).await;
+    let url = Url::parse("https://bit.ly/spam").unwrap();
+    let urls = state.context().urls;
+    let revision = state.config().scanner.web.revision;
+    urls.insert(&url, Url::parse("https://example.com/").unwrap(), None, revision);
+
+    handle_update(state.clone(), Ok(text_update(1, "/uncache https://bit.ly/spam")))
+        .await
+        .expect("failed to handle update");
+    let replies = api.calls("sendMessage");
+    assert_eq!(replies.len(), 1);
+    assert!(replies[0]["text"].as_str().unwrap().contains("Invalidated 1 of 1"));
+    assert!(urls.get(&url).is_none());
+}
+
+#[tokio::test]
+async fn operator_uncaches_url_cached_as_illegal() {
+    let api = FakeApi::default();
+    let state = operator_state(&api).await;
+    let url = Url::parse("https://bit.ly/spam").unwrap();
+    let urls = state.context().urls;
+    let verdict = Verdict::new(Kind::Url, Rule::Webpage { needle: "Binance".into() });
+    let revision = state.config().scanner.web.revision;
+    urls.insert(&url, Url::parse("https://example.com/").unwrap(), Some(verdict), revision);
+
+    // The command quotes the illegal URL, but is handled without auditing it
+    handle_update(state.clone(), Ok(text_update(1, "/uncache https://bit.ly/spam")))
+        .await
+        .expect("failed to handle update");
+    assert!(api.calls("kickChatMember").is_empty());
+    assert!(api.calls("deleteMessage").is_empty());
+    let replies = api.calls("sendMessage");
+    assert_eq!(replies.len(), 1);
+    assert!(replies[0]["text"].as_str().unwrap().contains("Invalidated 1 of 1"));
+    assert!(urls.get(&url).is_none());
+}
+
+#[tokio::test]
+async fn admin_cannot_uncache_url() {
+    let api = FakeApi::default();
+    let state = state(&api).await;
+    let url = Url::parse("https://bit.ly/spam").unwrap();
+    let urls = state.context().urls;
+    let revision = state.config().scanner.web.revision;
+    urls.insert(&url, Url::parse("https://example.com/").unwrap(), None, revision);
+
+    // The cache is shared by all chats, group administrators can't flush it
+    handle_update(state.clone(), Ok(text_update(1, "/uncache https://bit.ly/spam")))
+        .await
+        .expect("failed to handle update");
+    let replies = api.calls("sendMessage");
+    assert_eq!(replies.len(), 1);
+    assert!(replies[0]["text"].as_str().unwrap().contains("Only bot operators"));
+    assert!(urls.get(&url).is_some());
+}
+
+#[tokio::test]
+async fn audits_commands_from_non_admins() {
+    let api = FakeApi::default();
+    api.fail("getChatMember");
+    let state = state(&api).await;
+
+    // Spam doesn't get through by posing as a command
+    handle_update(state, Ok(text_update(1, "/uncache Only the first 5000 users will be rewarded")))
+        .await
+        .expect("failed to handle update");
+    assert_eq!(api.calls("kickChatMember").len(), 1);
+}
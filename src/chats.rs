@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use ban_binance_bot::config::chat::{self, ChatSettings};
+use tempfile::NamedTempFile;
+use telegram_bot::types::ChatId;
+
+/// Persistent store of per-chat settings.
+///
+/// Settings are kept in memory, and written to a JSON file on each change if a path is set.
+#[derive(Clone, Default)]
+pub struct ChatStore {
+    /// File the settings are stored in.
+    path: Option<PathBuf>,
+
+    /// Settings for each chat that has any.
+    chats: Arc<RwLock<HashMap<i64, ChatSettings>>>,
+}
+
+impl ChatStore {
+    /// Load the store from the given file.
+    ///
+    /// If the file doesn't exist yet, the store starts empty.
+    pub fn load<P: Into<PathBuf>>(path: P) -> Result<Self, Error> {
+        let path = path.into();
+        let chats = match fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data).map_err(Error::Json)?,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(err) => return Err(Error::Io(err)),
+        };
+
+        Ok(Self {
+            path: Some(path),
+            chats: Arc::new(RwLock::new(chats)),
+        })
+    }
+
+    /// Get the settings for the given chat.
+    pub fn get(&self, chat: ChatId) -> ChatSettings {
+        self.chats
+            .read()
+            .unwrap()
+            .get(&i64::from(chat))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Update the settings for the given chat, and persist the store.
+    ///
+    /// Nothing is changed if `update` fails, or if the store can't be persisted.
+    pub fn update<F>(&self, chat: ChatId, update: F) -> Result<ChatSettings, Error>
+    where
+        F: FnOnce(&mut ChatSettings) -> Result<(), chat::Error>,
+    {
+        let mut chats = self.chats.write().unwrap();
+        let mut settings = chats.get(&i64::from(chat)).cloned().unwrap_or_default();
+        update(&mut settings).map_err(Error::Setting)?;
+
+        // Change a copy, don't keep chats without any settings
+        let mut changed = chats.clone();
+        if settings.is_empty() {
+            changed.remove(&i64::from(chat));
+        } else {
+            changed.insert(chat.into(), settings.clone());
+        }
+
+        // Only apply the change once it's persisted
+        if let Some(path) = &self.path {
+            save(path, &changed)?;
+        }
+        *chats = changed;
+        Ok(settings)
+    }
+}
+
+/// Write the given chat settings to a file.
+///
+/// A temporary file is written first and then moved into place, so the file is never left
+/// partially written.
+fn save(path: &Path, chats: &HashMap<i64, ChatSettings>) -> Result<(), Error> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let mut file = NamedTempFile::new_in(dir).map_err(Error::Io)?;
+    let data = serde_json::to_vec_pretty(chats).map_err(Error::Json)?;
+    file.write_all(&data).map_err(Error::Io)?;
+    file.persist(path).map_err(|err| Error::Io(err.error))?;
+    Ok(())
+}
+
+/// A chat store error.
+#[derive(Debug)]
+pub enum Error {
+    /// Failed to read or write the store file.
+    Io(io::Error),
+
+    /// Failed to parse or serialize the store file.
+    Json(serde_json::Error),
+
+    /// Failed to change a setting.
+    Setting(chat::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_not_persisted() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ChatStore::load(dir.path().join("missing").join("chats.json")).unwrap();
+        let chat = ChatId::new(-1);
+
+        // The change is dropped if it can't be saved
+        let result = store.update(chat, |settings| settings.set("notify", "false"));
+        match result {
+            Err(Error::Io(_)) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+        assert!(store.get(chat).is_empty());
+    }
+}
@@ -0,0 +1,190 @@
+//! Statistical spam classifier.
+//!
+//! A naive Bayes classifier over token n-grams of message text. Tokens are taken from the
+//! skeleton of the text, so obfuscated text is classified like the text it resembles.
+//!
+//! A model is trained on a labelled corpus of spam and ham samples with the `train` command, and
+//! saved as JSON file. Set `scanner.classifier.model` to load it.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::util::text::words;
+
+/// The supported model format version.
+pub const VERSION: u32 = 1;
+
+/// Default size of the largest token n-gram to train on.
+pub const DEFAULT_NGRAMS: usize = 2;
+
+/// A trained naive Bayes model.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Model {
+    /// The model format version.
+    pub version: u32,
+
+    /// Size of the largest token n-gram, all smaller n-grams are used as well.
+    pub ngrams: usize,
+
+    /// Number of trained spam and ham samples.
+    pub samples: [u64; 2],
+
+    /// Total number of trained spam and ham tokens.
+    pub totals: [u64; 2],
+
+    /// Number of occurrences of each token in spam and ham samples.
+    pub tokens: BTreeMap<String, [u64; 2]>,
+}
+
+impl Model {
+    /// Train a model on the given samples, each being some text and whether it is spam.
+    ///
+    /// Fails if there are no spam or no ham samples.
+    pub fn train<'a, I>(ngrams: usize, samples: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = (&'a str, bool)>,
+    {
+        let mut model = Model {
+            version: VERSION,
+            ngrams: ngrams.max(1),
+            samples: [0, 0],
+            totals: [0, 0],
+            tokens: BTreeMap::new(),
+        };
+
+        for (text, spam) in samples {
+            let class = if spam { 0 } else { 1 };
+            model.samples[class] += 1;
+            for token in tokens(text, model.ngrams) {
+                model.totals[class] += 1;
+                model.tokens.entry(token).or_insert([0, 0])[class] += 1;
+            }
+        }
+
+        if model.samples.contains(&0) {
+            return Err(Error::Empty);
+        }
+        Ok(model)
+    }
+
+    /// Load a model from the given file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let data = fs::read_to_string(path).map_err(Error::Io)?;
+        let model: Model = serde_json::from_str(&data).map_err(Error::Json)?;
+        if model.version != VERSION {
+            return Err(Error::Version(model.version));
+        }
+        Ok(model)
+    }
+
+    /// Save the model to the given file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let data = serde_json::to_string(self).map_err(Error::Json)?;
+        fs::write(path, data).map_err(Error::Io)
+    }
+
+    /// Get the probability that the given text is spam, between 0 and 1.
+    ///
+    /// Tokens the model has not seen are ignored. Returns the ratio of spam samples if no token
+    /// is known.
+    pub fn probability(&self, text: &str) -> f64 {
+        let vocabulary = self.tokens.len() as f64;
+        let mut log = [
+            (self.samples[0] as f64).ln(),
+            (self.samples[1] as f64).ln(),
+        ];
+
+        // Sum token log likelihoods, with add-one smoothing
+        for token in tokens(text, self.ngrams) {
+            if let Some(counts) = self.tokens.get(&token) {
+                for (class, log) in log.iter_mut().enumerate() {
+                    let total = self.totals[class] as f64 + vocabulary;
+                    *log += ((counts[class] + 1) as f64 / total).ln();
+                }
+            }
+        }
+
+        1.0 / (1.0 + (log[1] - log[0]).exp())
+    }
+}
+
+/// Split the given text into tokens, being all word n-grams up to the given size.
+///
+/// Words are the alphanumeric parts of the skeleton of the text.
+pub fn tokens(text: &str, ngrams: usize) -> Vec<String> {
+    let words = words(text);
+    (1..=ngrams)
+        .flat_map(|n| words.windows(n).map(|window| window.join(" ")))
+        .collect()
+}
+
+/// A classifier error.
+#[derive(Debug)]
+pub enum Error {
+    /// Failed to read or write the model file.
+    Io(io::Error),
+
+    /// The model file is not valid JSON.
+    Json(serde_json::Error),
+
+    /// The model has an unsupported format version.
+    Version(u32),
+
+    /// There are no spam or no ham samples to train on.
+    Empty,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "failed to read or write model: {}", err),
+            Error::Json(err) => write!(f, "invalid model: {}", err),
+            Error::Version(version) => write!(f, "unsupported model version {}", version),
+            Error::Empty => write!(f, "need both spam and ham samples to train on"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens() {
+        assert_eq!(
+            tokens("Βἱnаⴖcе US, event!", 2),
+            ["binance", "us", "event", "binance us", "us event"],
+        );
+        assert!(tokens("!!", 2).is_empty());
+    }
+
+    #[test]
+    fn test_train() {
+        let samples = vec![
+            ("Only the first 5000 users will be rewarded", true),
+            ("Celebrating our new crypto exchange, deposit bonus for participants", true),
+            ("Event ends today! Deposit now for a 10 times bonus", true),
+            ("Good morning everyone, how is the market today?", false),
+            ("Does anyone know how to withdraw from the exchange?", false),
+            ("The new release is out, see the changelog", false),
+        ];
+        let model = Model::train(2, samples.iter().cloned()).unwrap();
+        assert!(model.probability("Deposit bonus for the first 5000 participants") > 0.9);
+        assert!(model.probability("Good morning, how is everyone?") < 0.1);
+
+        // The model survives a round trip through a file
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.json");
+        model.save(&path).unwrap();
+        let loaded = Model::load(&path).unwrap();
+        assert_eq!(loaded.tokens, model.tokens);
+
+        assert!(match Model::train(2, vec![("spam", true)]) {
+            Err(Error::Empty) => true,
+            _ => false,
+        });
+    }
+}
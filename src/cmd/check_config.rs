@@ -0,0 +1,30 @@
+use ban_binance_bot::config::{
+    lint::{self, Severity},
+    Config,
+};
+use clap::ArgMatches;
+
+/// Invoke the check-config command.
+///
+/// This loads the configuration at the given path with environment overrides applied, and
+/// reports all issues with their key path. Returns whether the configuration passed.
+pub fn invoke(matches: &ArgMatches<'_>, path: &str) -> bool {
+    let config = match Config::load(path) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("error: {}: {}", path, err);
+            return false;
+        }
+    };
+
+    let issues = lint::lint(&config);
+    for issue in &issues {
+        eprintln!("{}", issue);
+    }
+
+    let errors = issues.iter().filter(|i| i.severity == Severity::Error).count();
+    let warnings = issues.len() - errors;
+    eprintln!("{}: {} error(s), {} warning(s)", path, errors, warnings);
+
+    errors == 0 && (warnings == 0 || !matches.is_present("deny-warnings"))
+}
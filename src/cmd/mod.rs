@@ -0,0 +1,117 @@
+pub mod check_config;
+pub mod scan;
+pub mod train;
+
+use clap::{App, AppSettings, Arg, ArgGroup, ArgMatches, SubCommand};
+
+/// Default configuration file path.
+const CONFIG_DEFAULT: &str = "./config.toml";
+
+/// Build the command line application definition.
+pub fn app<'a, 'b>() -> App<'a, 'b> {
+    App::new(crate_name!())
+        .version(crate_version!())
+        .about(crate_description!())
+        .setting(AppSettings::VersionlessSubcommands)
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .short("c")
+                .value_name("PATH")
+                .env("BAN_BOT_CONFIG")
+                .global(true)
+                .help("Configuration file to use [default: ./config.toml]"),
+        )
+        .subcommand(
+            SubCommand::with_name("check-config")
+                .about("Validate the configuration, and lint the scanner rules")
+                .arg(
+                    Arg::with_name("deny-warnings")
+                        .long("deny-warnings")
+                        .help("Fail on warnings as well"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("scan")
+                .about("Audit text, URLs and files offline, without connecting to Telegram")
+                .arg(
+                    Arg::with_name("text")
+                        .long("text")
+                        .short("t")
+                        .value_name("TEXT")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Text to audit"),
+                )
+                .arg(
+                    Arg::with_name("url")
+                        .long("url")
+                        .short("u")
+                        .value_name("URL")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("URL to audit"),
+                )
+                .arg(
+                    Arg::with_name("file")
+                        .long("file")
+                        .short("f")
+                        .value_name("PATH")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("File to audit, such as an image or video"),
+                )
+                .arg(
+                    Arg::with_name("dir")
+                        .long("dir")
+                        .short("d")
+                        .value_name("PATH")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Directory of files to audit"),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("Output results as JSON"),
+                )
+                .group(
+                    ArgGroup::with_name("input")
+                        .args(&["text", "url", "file", "dir"])
+                        .multiple(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("train")
+                .about("Train the spam classifier on a labelled corpus, and save the model")
+                .arg(
+                    Arg::with_name("corpus")
+                        .long("corpus")
+                        .value_name("PATH")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .required(true)
+                        .help("Corpus directory of spam and ham samples"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .short("o")
+                        .value_name("PATH")
+                        .required(true)
+                        .help("File to save the model to"),
+                )
+                .arg(
+                    Arg::with_name("ngrams")
+                        .long("ngrams")
+                        .value_name("N")
+                        .help("Size of the largest token n-gram to train on [default: 2]"),
+                ),
+        )
+}
+
+/// Get the configuration file path to use.
+pub fn config_path<'a>(matches: &'a ArgMatches) -> &'a str {
+    matches.value_of("config").unwrap_or(CONFIG_DEFAULT)
+}
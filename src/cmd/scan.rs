@@ -0,0 +1,210 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ban_binance_bot::{
+    config::{Action, Config},
+    feed,
+    scanner::{self, Verdict},
+};
+use clap::ArgMatches;
+use took::Timer;
+use url::Url;
+
+/// An input to audit.
+#[derive(Debug)]
+enum Input {
+    /// Plain text.
+    Text(String),
+
+    /// A URL.
+    Url(Url),
+
+    /// A file at the given path.
+    File(PathBuf),
+}
+
+impl Input {
+    /// Describe the input for human readable output.
+    fn describe(&self) -> String {
+        match self {
+            Input::Text(text) => format!("text {:?}", scanner::verdict::excerpt(text)),
+            Input::Url(url) => format!("URL {}", url),
+            Input::File(path) => format!("file {}", path.display()),
+        }
+    }
+
+    /// Get the kind of input, used in JSON output.
+    fn kind(&self) -> &'static str {
+        match self {
+            Input::Text(_) => "text",
+            Input::Url(_) => "url",
+            Input::File(_) => "file",
+        }
+    }
+
+    /// Get the raw input value, used in JSON output.
+    fn value(&self) -> String {
+        match self {
+            Input::Text(text) => text.clone(),
+            Input::Url(url) => url.to_string(),
+            Input::File(path) => path.display().to_string(),
+        }
+    }
+}
+
+/// The audit result for a single input.
+#[derive(Serialize)]
+struct Report {
+    /// The kind of input.
+    kind: &'static str,
+
+    /// The input value.
+    input: String,
+
+    /// Whether the input is illegal, and would be acted upon.
+    illegal: bool,
+
+    /// The score of the verdict.
+    score: f64,
+
+    /// The action that would be taken.
+    action: Action,
+
+    /// The verdict if any rule matched the input.
+    verdict: Option<Verdict>,
+
+    /// Time the audit took in milliseconds.
+    took_ms: u128,
+}
+
+/// Invoke the scan command.
+///
+/// This audits all given inputs with the scanners, using the given configuration and the cached
+/// copies of its rule feeds. No connection to the Telegram API is made.
+pub async fn invoke(matches: &ArgMatches<'_>, config: &Config) -> Result<(), Error> {
+    // Merge cached rule feeds
+    let mut config = config.clone();
+    for source in &config.feeds.sources.clone() {
+        match feed::load_cached(source, Path::new(&config.feeds.cache)) {
+            Ok(feed) => feed.merge_into(&mut config.scanner),
+            Err(err) => eprintln!("Not using rule feed '{}', no cached copy: {:?}", source, err),
+        }
+    }
+    config.scanner.compile();
+    let context = scanner::Context::new(&config);
+
+    // Collect all inputs to audit
+    let inputs = collect_inputs(matches)?;
+    let json = matches.is_present("json");
+
+    // Audit each input in order
+    let mut reports = vec![];
+    for input in inputs {
+        let timer = Timer::new();
+        let verdict = match &input {
+            Input::Text(text) => {
+                scanner::text::is_illegal_text(&config.scanner, &context, text.clone()).await
+            }
+            Input::Url(url) => {
+                let urls = vec![url.clone()];
+                scanner::url::any_illegal_url(&config.scanner.web, &context, urls, 0).await
+            }
+            Input::File(path) => {
+                scanner::file::is_illegal_file(&config.scanner, &context, path).await
+            }
+        };
+        let took = timer.took();
+        let scoring = &config.scanner.scoring;
+        let score = verdict.as_ref().map_or(0.0, |verdict| scoring.score(verdict));
+        let action = scoring.action(verdict.as_ref());
+        let illegal = action != Action::Ignore;
+
+        if !json {
+            match &verdict {
+                Some(verdict) if illegal => println!(
+                    "ILLEGAL {} (score {:.2}, action {:?}, took {})\n  {}",
+                    input.describe(),
+                    score,
+                    action,
+                    took,
+                    verdict,
+                ),
+                Some(verdict) => println!(
+                    "safe    {} (score {:.2}, took {})\n  {}",
+                    input.describe(),
+                    score,
+                    took,
+                    verdict,
+                ),
+                None => println!("safe    {} (took {})", input.describe(), took),
+            }
+        }
+
+        reports.push(Report {
+            kind: input.kind(),
+            input: input.value(),
+            illegal,
+            score,
+            action,
+            verdict,
+            took_ms: took.as_std().as_millis(),
+        });
+    }
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&reports).map_err(Error::Json)?,
+        );
+    }
+
+    Ok(())
+}
+
+/// Collect all inputs from the given command line arguments.
+///
+/// Directories are expanded into the files they contain, in sorted order.
+fn collect_inputs(matches: &ArgMatches<'_>) -> Result<Vec<Input>, Error> {
+    let mut inputs = vec![];
+
+    if let Some(texts) = matches.values_of("text") {
+        inputs.extend(texts.map(|text| Input::Text(text.into())));
+    }
+
+    if let Some(urls) = matches.values_of("url") {
+        for url in urls {
+            let url = Url::parse(url).map_err(|err| Error::Url(url.into(), err))?;
+            inputs.push(Input::Url(url));
+        }
+    }
+
+    if let Some(files) = matches.values_of("file") {
+        inputs.extend(files.map(|path| Input::File(path.into())));
+    }
+
+    if let Some(dirs) = matches.values_of("dir") {
+        for dir in dirs {
+            let mut paths = fs::read_dir(dir)
+                .and_then(|entries| entries.map(|entry| entry.map(|e| e.path())).collect::<Result<Vec<_>, _>>())
+                .map_err(|err| Error::Dir(dir.into(), err))?;
+            paths.retain(|path| path.is_file());
+            paths.sort();
+            inputs.extend(paths.into_iter().map(Input::File));
+        }
+    }
+
+    Ok(inputs)
+}
+
+/// A scan command error.
+#[derive(Debug)]
+pub enum Error {
+    /// Failed to parse a given URL.
+    Url(String, url::ParseError),
+
+    /// Failed to list files in the given directory.
+    Dir(PathBuf, std::io::Error),
+
+    /// Failed to serialize output as JSON.
+    Json(serde_json::Error),
+}
@@ -0,0 +1,64 @@
+use std::path::PathBuf;
+
+use ban_binance_bot::{
+    classifier::{self, Model, DEFAULT_NGRAMS},
+    corpus::{self, Expect},
+};
+use clap::ArgMatches;
+
+/// Invoke the train command.
+///
+/// This trains the spam classifier on the text of all samples in the given corpus directories,
+/// and saves the model. Samples without text are skipped. No configuration is needed, so a model
+/// can be trained before the configuration refers to it.
+pub fn invoke(matches: &ArgMatches<'_>) -> Result<(), Error> {
+    let ngrams = match matches.value_of("ngrams") {
+        Some(ngrams) => ngrams.parse().map_err(|_| Error::Ngrams(ngrams.into()))?,
+        None => DEFAULT_NGRAMS,
+    };
+
+    // Load all samples from the corpus directories
+    let mut samples = vec![];
+    for dir in matches.values_of("corpus").into_iter().flatten() {
+        samples.extend(corpus::load_dir(dir).map_err(Error::Corpus)?);
+    }
+    let texts: Vec<(&str, bool)> = samples
+        .iter()
+        .filter_map(|sample| {
+            let text = sample.text.as_ref()?;
+            Some((text.as_str(), sample.expect == Expect::Illegal))
+        })
+        .collect();
+
+    // Train and save the model
+    let model = Model::train(ngrams, texts.iter().cloned()).map_err(Error::Model)?;
+    let output = matches.value_of("output").unwrap();
+    model
+        .save(output)
+        .map_err(|err| Error::Save(output.into(), err))?;
+
+    println!(
+        "Trained classifier on {} spam and {} ham samples ({} tokens), saved to {}",
+        model.samples[0],
+        model.samples[1],
+        model.tokens.len(),
+        output,
+    );
+    Ok(())
+}
+
+/// A train command error.
+#[derive(Debug)]
+pub enum Error {
+    /// The given n-gram size is not a number.
+    Ngrams(String),
+
+    /// Failed to load a corpus directory.
+    Corpus(corpus::Error),
+
+    /// Failed to train the model.
+    Model(classifier::Error),
+
+    /// Failed to save the model to the given path.
+    Save(PathBuf, classifier::Error),
+}
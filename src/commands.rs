@@ -0,0 +1,204 @@
+use ban_binance_bot::util;
+use telegram_bot::{
+    prelude::*,
+    types::{ChatMemberStatus, GetChatMember, Message, MessageKind},
+};
+use url::Url;
+
+use crate::{chats, state::State};
+
+/// Commands only administrators may use.
+const ADMIN_COMMANDS: [&str; 2] = ["set", "reset"];
+
+/// Commands only bot operators may use, because they affect all chats.
+const OPERATOR_COMMANDS: [&str; 1] = ["uncache"];
+
+/// A command for this bot, parsed from a group message.
+pub struct Command<'a> {
+    /// The command name, without slash and bot username.
+    name: &'a str,
+
+    /// The command arguments.
+    args: Vec<&'a str>,
+
+    /// Whether the sender may use this command, only checked for administrator and operator
+    /// commands.
+    allowed: bool,
+}
+
+impl<'a> Command<'a> {
+    /// Parse the given group message as a command for this bot.
+    ///
+    /// Returns `None` if the message isn't a supported command for this bot.
+    pub async fn parse(state: &State, msg: &'a Message) -> Option<Command<'a>> {
+        let text = match &msg.kind {
+            MessageKind::Text { data, .. } => data,
+            _ => return None,
+        };
+        let mut words = text.split_whitespace();
+        let name = words.next().and_then(|word| parse_command(state, word))?;
+        let allowed = if ADMIN_COMMANDS.contains(&name) {
+            is_admin(state, msg).await
+        } else if OPERATOR_COMMANDS.contains(&name) {
+            is_operator(state, msg)
+        } else if name == "settings" {
+            false
+        } else {
+            return None;
+        };
+
+        Some(Command {
+            name,
+            args: words.collect(),
+            allowed,
+        })
+    }
+
+    /// Check whether this is an administrator or operator command, sent by a user allowed to
+    /// use it.
+    ///
+    /// These are trusted, and are handled without auditing the message. Arguments may quote
+    /// spam, such as a URL to uncache.
+    pub fn is_trusted(&self) -> bool {
+        self.allowed
+    }
+}
+
+/// Handle the given command for this bot, and reply with the result.
+///
+/// Supported commands:
+/// - `/settings`: show the settings for this chat
+/// - `/set <key> <value>`: change a setting, for administrators only
+/// - `/reset <key>`: reset a setting to the global default, for administrators only
+/// - `/uncache <url>...`: audit the given URLs again instead of using cached audits, for bot
+///   operators only
+pub async fn handle(state: &State, msg: &Message, command: Command<'_>) {
+    let args = &command.args;
+    let reply = match command.name {
+        "set" | "reset" if !command.allowed => {
+            "Only group administrators can change settings.".into()
+        }
+        "uncache" if !command.allowed => {
+            "Only bot operators can invalidate cached URL audits.".into()
+        }
+        "set" => set(state, msg, args),
+        "reset" => reset(state, msg, args),
+        "uncache" => uncache(state, args),
+        _ => settings(state, msg),
+    };
+
+    if let Err(err) = state
+        .telegram_client()
+        .send(msg.text_reply(reply).disable_preview())
+        .await
+    {
+        warn!("Failed to reply to command: {:?}", err);
+    }
+}
+
+/// Parse a command word, such as `/set` or `/set@banbinancebot`.
+///
+/// Returns `None` if the word is not a command, or if it is a command for another bot.
+fn parse_command<'a>(state: &State, word: &'a str) -> Option<&'a str> {
+    if !word.starts_with('/') {
+        return None;
+    }
+
+    let mut parts = word[1..].splitn(2, '@');
+    let command = parts.next()?;
+    match parts.next() {
+        Some(user) if !state.username().map_or(false, |u| u.eq_ignore_ascii_case(user)) => None,
+        _ => Some(command),
+    }
+}
+
+/// Describe the settings for the chat.
+fn settings(state: &State, msg: &Message) -> String {
+    format!(
+        "Settings for this chat:\n\n{}\n\nAdministrators can change these with /set <setting> <value>, \
+        or use /reset <setting> to use the default again.",
+        state.chats().get(msg.chat.id()).describe(&state.config()),
+    )
+}
+
+/// Change a setting for the chat.
+fn set(state: &State, msg: &Message, args: &[&str]) -> String {
+    let (key, value) = match args {
+        [key, value] => (key, value),
+        _ => return "Usage: /set <setting> <value>".into(),
+    };
+
+    match state.chats().update(msg.chat.id(), |settings| settings.set(key, value)) {
+        Ok(settings) => {
+            info!("Changed setting '{}' to '{}' in chat {}", key, value, msg.chat.id());
+            format!("Changed {} to {}.\n\n{}", key, value, settings.describe(&state.config()))
+        }
+        Err(chats::Error::Setting(err)) => format!("Can't change {}: {}.", key, err),
+        Err(err) => {
+            warn!("Failed to change chat setting: {:?}", err);
+            "Failed to change setting, please try again later.".into()
+        }
+    }
+}
+
+/// Reset a setting for the chat to the global default.
+fn reset(state: &State, msg: &Message, args: &[&str]) -> String {
+    let key = match args {
+        [key] => key,
+        _ => return "Usage: /reset <setting>".into(),
+    };
+
+    match state.chats().update(msg.chat.id(), |settings| settings.reset(key)) {
+        Ok(settings) => {
+            info!("Reset setting '{}' in chat {}", key, msg.chat.id());
+            format!("Reset {}.\n\n{}", key, settings.describe(&state.config()))
+        }
+        Err(chats::Error::Setting(err)) => format!("Can't reset {}: {}.", key, err),
+        Err(err) => {
+            warn!("Failed to reset chat setting: {:?}", err);
+            "Failed to reset setting, please try again later.".into()
+        }
+    }
+}
+
+/// Invalidate the cached audits of the given URLs, so they are audited again.
+fn uncache(state: &State, args: &[&str]) -> String {
+    let urls: Vec<Url> = args.iter().flat_map(|arg| util::url::find_urls(arg)).collect();
+    if urls.is_empty() {
+        return "Usage: /uncache <url>...".into();
+    }
+
+    let invalidated = urls
+        .iter()
+        .filter(|url| state.context().urls.invalidate(url))
+        .count();
+    info!("Invalidated {} of {} cached URL audit(s)", invalidated, urls.len());
+    format!(
+        "Invalidated {} of {} cached URL audit(s), these URLs are audited again when posted.",
+        invalidated,
+        urls.len(),
+    )
+}
+
+/// Check whether the sender of the given message is an administrator of the chat.
+/// Check whether the sender of the given message is a configured bot operator.
+fn is_operator(state: &State, msg: &Message) -> bool {
+    state.config().general.operators.contains(&i64::from(msg.from.id))
+}
+
+async fn is_admin(state: &State, msg: &Message) -> bool {
+    match state
+        .telegram_client()
+        .send(GetChatMember::new(&msg.chat, &msg.from))
+        .await
+    {
+        Ok(member) => match member.status {
+            ChatMemberStatus::Creator | ChatMemberStatus::Administrator => true,
+            _ => false,
+        },
+        Err(err) => {
+            warn!("Failed to check whether user is chat administrator: {:?}", err);
+            false
+        }
+    }
+}
@@ -1,16 +1,192 @@
+use std::collections::HashSet;
+use std::env;
+use std::fmt;
 use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-#[derive(Debug, Deserialize)]
+use sha2::{Digest, Sha256};
+use toml::{value::Table, Value};
+
+use lint::Severity;
+use matcher::Matcher;
+use pattern::Pattern;
+
+use crate::{
+    classifier::{self, Model},
+    scanner::verdict::{Rule, Verdict},
+    util::{address, text::skeleton},
+};
+
+pub mod chat;
+mod include;
+pub mod lint;
+pub mod matcher;
+pub mod pattern;
+pub mod rules;
+
+/// Prefix for environment variables overriding configuration keys.
+///
+/// Nested keys are separated by a double underscore, for example
+/// `BAN_BOT__GENERAL__NOTIFICATION_SELF_DESTRUCT=30` overrides
+/// `general.notification_self_destruct`.
+pub const ENV_PREFIX: &str = "BAN_BOT__";
+
+/// Legacy environment variables, and the configuration keys they override.
+const ENV_LEGACY: [(&str, &str); 2] = [
+    ("TELEGRAM_BOT_TOKEN", "telegram.token"),
+    ("GLOBAL_SPAM_LOG_CHAT_ID", "general.spam_log_chat"),
+];
+
+#[derive(Debug, Deserialize, Clone)]
 pub struct Config {
+    /// Rule files to merge into the scanner rules, such as `rules.d/*.toml`.
+    ///
+    /// Paths are relative to the configuration file. File names may contain `*` and `?`
+    /// wildcards.
+    #[serde(default)]
+    pub include: Vec<String>,
+
+    /// Rule files, and directories of wildcard patterns, that rules were included from.
+    #[serde(skip)]
+    pub included: Vec<PathBuf>,
+
     pub general: General,
+    #[serde(default)]
+    pub telegram: Telegram,
     pub scanner: Scanner,
+    #[serde(default)]
+    pub feeds: Feeds,
+    #[serde(default)]
+    pub fingerprints: Fingerprints,
+    #[serde(default)]
+    pub archive: Archive,
+    #[serde(default)]
+    pub http: Http,
+    #[serde(default)]
+    pub url_cache: UrlCache,
 }
 
 impl Config {
-    /// Load the configuration from the given path.
+    /// Load the configuration from the given path, and merge included rule files into it.
     pub fn from_path(path: &str) -> Result<Self, Error> {
-        toml::from_str(&fs::read_to_string(path).map_err(Error::Read)?).map_err(Error::Toml)
+        let config = toml::from_str(&fs::read_to_string(path).map_err(Error::Read)?)
+            .map_err(Error::Toml)?;
+        Self::finish(config, path)
+    }
+
+    /// Load the configuration from the given path, and apply environment variable overrides.
+    ///
+    /// Legacy environment variables are applied first, then variables prefixed with
+    /// `ENV_PREFIX`. Empty variables are ignored. Included rule files are merged last, so
+    /// the `include` list itself can be overridden as well.
+    pub fn load(path: &str) -> Result<Self, Error> {
+        let mut value: Value =
+            toml::from_str(&fs::read_to_string(path).map_err(Error::Read)?).map_err(Error::Toml)?;
+
+        // Collect overrides from the environment
+        let legacy = ENV_LEGACY
+            .iter()
+            .filter_map(|(var, key)| env::var(var).ok().map(|raw| (key.to_string(), raw)));
+        let prefixed = env::vars().filter_map(|(var, raw)| {
+            if !var.starts_with(ENV_PREFIX) {
+                return None;
+            }
+            let key = var[ENV_PREFIX.len()..].to_lowercase().replace("__", ".");
+            Some((key, raw))
+        });
+
+        for (key, raw) in legacy.chain(prefixed) {
+            if raw.trim().is_empty() {
+                continue;
+            }
+            debug!("Overriding configuration key '{}' from environment", key);
+            set_key(&mut value, &key, parse_value(&raw))?;
+        }
+
+        Self::finish(value.try_into().map_err(Error::Toml)?, path)
+    }
+
+    /// Merge the rule files included by the configuration at the given path into it, compile
+    /// the scanner rules, and load the classifier model.
+    ///
+    /// All configured paths are relative to the configuration file.
+    fn finish(mut config: Self, path: &str) -> Result<Self, Error> {
+        let base = Path::new(path).parent().unwrap_or_else(|| Path::new("."));
+        let paths = vec![
+            &mut config.general.chat_settings,
+            &mut config.archive.path,
+            &mut config.url_cache.path,
+            &mut config.scanner.image.dir,
+        ];
+        for path in paths.into_iter().filter_map(Option::as_mut) {
+            *path = resolve(base, path);
+        }
+        config.feeds.cache = resolve(base, &config.feeds.cache);
+
+        include::apply(&mut config, base)?;
+        config.scanner.compile();
+        config.scanner.classifier.load(base)?;
+        Ok(config)
+    }
+
+    /// Validate the configuration.
+    ///
+    /// This catches mistakes that would otherwise only surface while auditing messages, such as
+    /// a missing image directory. Use this before replacing a running configuration.
+    /// Only lint errors fail validation, use `lint::lint` to list warnings as well.
+    pub fn validate(&self) -> Result<(), Error> {
+        let errors: Vec<String> = lint::lint(self)
+            .into_iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .map(|issue| format!("{}: {}", issue.key, issue.message))
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Invalid(errors.join("; ")))
+        }
+    }
+}
+
+/// Resolve the given path relative to the given directory, absolute paths are kept.
+fn resolve(base: &Path, path: &str) -> String {
+    base.join(path).to_string_lossy().into_owned()
+}
+
+/// Parse a raw configuration value from the environment.
+///
+/// The value is parsed as TOML value, such as a number, boolean or array. If that fails, it is
+/// used as plain string.
+fn parse_value(raw: &str) -> Value {
+    toml::from_str::<Value>(&format!("value = {}", raw))
+        .ok()
+        .and_then(|mut table| table.as_table_mut().and_then(|table| table.remove("value")))
+        .unwrap_or_else(|| Value::String(raw.into()))
+}
+
+/// Set the configuration key at the given dotted path to the given value.
+///
+/// Missing tables along the path are created.
+fn set_key(root: &mut Value, key: &str, value: Value) -> Result<(), Error> {
+    let mut parts: Vec<&str> = key.split('.').collect();
+    let last = parts.pop().unwrap();
+
+    let mut table = root;
+    for part in parts {
+        table = table
+            .as_table_mut()
+            .ok_or_else(|| Error::Override(key.into()))?
+            .entry(part)
+            .or_insert(Value::Table(Table::new()));
     }
+
+    table
+        .as_table_mut()
+        .ok_or_else(|| Error::Override(key.into()))?
+        .insert(last.into(), value);
+    Ok(())
 }
 
 #[derive(Debug)]
@@ -20,30 +196,220 @@ pub enum Error {
 
     /// Toml format error.
     Toml(toml::de::Error),
+
+    /// Failed to override the given key, a parent key is not a table.
+    Override(String),
+
+    /// The configuration is invalid, with a description of the problem.
+    Invalid(String),
+
+    /// Failed to include the given rule file or directory.
+    Include(PathBuf, Box<Error>),
+
+    /// Failed to load the given classifier model.
+    Model(PathBuf, classifier::Error),
 }
 
-#[derive(Debug, Deserialize)]
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Read(err) => write!(f, "failed to read configuration: {}", err),
+            Error::Toml(err) => write!(f, "invalid configuration: {}", err),
+            Error::Override(key) => write!(f, "can't override '{}' from environment", key),
+            Error::Invalid(description) => write!(f, "invalid configuration: {}", description),
+            Error::Include(path, err) => write!(f, "{}: {}", path.display(), err),
+            Error::Model(path, err) => write!(f, "{}: {}", path.display(), err),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
 pub struct General {
-    pub notification_self_destruct: Option<u64>,
+    /// Post a notification in the chat when a user is banned.
+    #[serde(default = "default_notify")]
+    pub notify: bool,
+
+    /// Time in seconds after which to self-destruct ban notification messages by this bot.
+    ///
+    /// Set to `0` to not self-destruct.
+    #[serde(default = "default_notification_self_destruct")]
+    pub notification_self_destruct: u64,
+
+    /// Number of Telegram API updates to process concurrently.
+    #[serde(default = "default_concurrent_updates")]
+    pub concurrent_updates: usize,
+
+    /// Chat ID of the spam log chat, all detected spam is forwarded to it.
+    #[serde(default)]
+    pub spam_log_chat: Option<i64>,
+
+    /// File to persistently store per-chat settings in.
+    ///
+    /// If not set, per-chat settings are lost when the bot restarts.
+    #[serde(default)]
+    pub chat_settings: Option<String>,
+
+    /// Maximum time in seconds to wait for in-flight updates when shutting down.
+    #[serde(default = "default_shutdown_timeout")]
+    pub shutdown_timeout: u64,
+
+    /// User IDs of bot operators, who may use commands that affect all chats, such as `/uncache`.
+    #[serde(default)]
+    pub operators: Vec<i64>,
+}
+
+fn default_notify() -> bool {
+    true
+}
+
+fn default_notification_self_destruct() -> u64 {
+    60
+}
+
+fn default_concurrent_updates() -> usize {
+    num_cpus::get().max(2)
+}
+
+fn default_shutdown_timeout() -> u64 {
+    30
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct Telegram {
+    /// The Telegram bot token.
+    #[serde(default)]
+    pub token: Option<String>,
+
+    /// Receive updates through a webhook, instead of long polling.
+    #[serde(default)]
+    pub webhook: Option<Webhook>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Webhook {
+    /// Public URL Telegram sends updates to.
+    ///
+    /// The path of this URL is the path the webhook listener accepts updates on.
+    pub url: String,
+
+    /// Address the webhook listener binds to.
+    #[serde(default = "default_webhook_listen")]
+    pub listen: SocketAddr,
+
+    /// Secret token Telegram must send along with each update, required.
+    ///
+    /// Updates without it are rejected, so nobody else can send forged updates to the listener.
+    #[serde(default)]
+    pub secret: Option<String>,
+}
+
+fn default_webhook_listen() -> SocketAddr {
+    ([127, 0, 0, 1], 8080).into()
+}
+
+#[derive(Debug, Deserialize, Clone)]
 pub struct Scanner {
     pub text: Text,
     pub web: Web,
     pub image: Image,
+    #[serde(default)]
+    pub file: File,
+    #[serde(default)]
+    pub address: Address,
+    #[serde(default)]
+    pub classifier: Classifier,
+    #[serde(default)]
+    pub scoring: Scoring,
 }
 
-#[derive(Debug, Deserialize)]
+impl Scanner {
+    /// Compile the text rule lists into matchers.
+    ///
+    /// This must be called again after changing the rule lists, such as when merging rules.
+    pub fn compile(&mut self) {
+        self.text.matcher = Matcher::new(&self.text.text, skeleton);
+        self.web.compile();
+        self.scoring.matcher =
+            Matcher::new(self.scoring.phrases.iter().map(|phrase| &phrase.rule), skeleton);
+        self.address.compile();
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
 pub struct Text {
-    pub text: Vec<String>,
+    /// Illegal message text, as literal text or regex rules, see `Pattern`.
+    pub text: Vec<Pattern>,
+
+    /// Matcher compiled from `text`, matching the skeleton of message text.
+    #[serde(skip)]
+    pub matcher: Matcher,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Clone)]
 pub struct Web {
-    pub hosts: Vec<String>,
-    pub host_parts: Vec<String>,
-    pub text: Vec<String>,
+    /// Illegal hosts, as literal hosts or regex rules, see `Pattern`.
+    pub hosts: Vec<Pattern>,
+
+    /// Illegal parts of hosts, as literal text or regex rules, see `Pattern`.
+    pub host_parts: Vec<Pattern>,
+
+    /// Illegal webpage content, as literal text or regex rules, see `Pattern`.
+    pub text: Vec<Pattern>,
+
+    /// Matcher compiled from `text`, matching lowercased webpage bodies.
+    #[serde(skip)]
+    pub text_matcher: Matcher,
+
+    /// Hosts of URLs that are scanned as well if appearing on a webpage, such as URL shorteners.
+    #[serde(default = "default_web_scan_hosts")]
+    pub scan_hosts: Vec<String>,
+
+    /// The max depth for scanning URLs.
+    ///
+    /// This manages how deep this bot will go with scanning URLs on webpages recursively.
+    /// Following many URL redirects counts as 1 depth.
+    #[serde(default = "default_web_max_depth")]
+    pub max_depth: usize,
+
+    /// Revision of the rules above, changes when any of them changes.
+    ///
+    /// Cached URL audits made with another revision are not trusted.
+    #[serde(skip)]
+    pub revision: u64,
+}
+
+impl Web {
+    /// Compile the webpage text rules into a matcher, and determine the rules revision.
+    fn compile(&mut self) {
+        self.text_matcher = Matcher::new(&self.text, |text: &str| text.to_lowercase());
+
+        // Hash every rule, separated by kind and list
+        let mut hasher = Sha256::new();
+        for list in &[&self.hosts, &self.host_parts, &self.text] {
+            for rule in list.iter() {
+                hasher.input(&[rule.is_regex() as u8]);
+                hasher.input(rule.as_str().as_bytes());
+                hasher.input(&[0]);
+            }
+            hasher.input(&[1]);
+        }
+        for host in &self.scan_hosts {
+            hasher.input(host.as_bytes());
+            hasher.input(&[0]);
+        }
+        hasher.input(&(self.max_depth as u64).to_be_bytes());
+        self.revision = hasher.result()[..8]
+            .iter()
+            .fold(0u64, |revision, byte| (revision << 8) | u64::from(*byte));
+    }
+}
+
+fn default_web_scan_hosts() -> Vec<String> {
+    vec!["bit.ly".into(), "t.cn".into(), "t.co".into(), "tinyurl.com".into()]
+}
+
+fn default_web_max_depth() -> usize {
+    3
 }
 
 // TODO: do not allow cloning this, use references
@@ -53,46 +419,725 @@ pub struct Image {
     pub dir: Option<String>,
     pub threshold: f32,
     pub text: Vec<String>,
+
+    /// When auditing, read text from images with OCR to match against `text`.
+    ///
+    /// This has no effect if the `ocr` feature is not enabled.
+    #[serde(default = "default_image_ocr")]
+    pub ocr: bool,
+
+    /// When auditing, compare images against the illegal image templates.
+    ///
+    /// This is expensive when lots of images are listed as illegal.
+    #[serde(default = "default_image_compare")]
+    pub compare: bool,
+
+    /// The maximum file size in bytes of images to check for legality.
+    #[serde(default = "default_image_max_file_size")]
+    pub max_file_size: i64,
+
+    /// The minimum number of pixels each image side must have.
+    ///
+    /// This is for image matching. Image OCR will run on all images if enabled.
+    #[serde(default = "default_image_min_size")]
+    pub min_size: u32,
+
+    /// Number of image templates to match against at the same time, for each image.
+    #[serde(default = "default_image_concurrent_matches")]
+    pub concurrent_matches: usize,
+
+    /// Additional directories containing illegal images, such as those from rule feeds.
+    #[serde(skip)]
+    pub template_dirs: Vec<PathBuf>,
 }
 
-/// A list of hosts for URLs that should be scanned if appearing on the webpage.
-pub const SCAN_WEBPAGE_URL_HOSTS: [&str; 4] = ["bit.ly", "t.cn", "t.co", "tinyurl.com"];
+fn default_image_ocr() -> bool {
+    true
+}
 
-/// The maximum file size in bytes of files to check for legality.
-pub const MAX_FILE_SIZE: i64 = 100 * 1024 * 1024;
+fn default_image_compare() -> bool {
+    true
+}
 
-/// The maximum file size in bytes of images to check for legality.
-pub const IMAGE_MAX_FILE_SIZE: i64 = 20 * 1024 * 1024;
+fn default_image_max_file_size() -> i64 {
+    20 * 1024 * 1024
+}
 
-/// The minimum number of pixels each image side must have.
-///
-/// This is for image matching. Image OCR will run on all images if enabled.
-pub const IMAGE_MIN_SIZE: u32 = 80;
+fn default_image_min_size() -> u32 {
+    80
+}
 
-/// The max depth for scanning URLs.
-///
-/// This manages how deep this bot will go with scanning URLs on webpages recursively.
-/// Following many URL redirects counts as 1 depth.
-pub const MAX_DEPTH: usize = 3;
+fn default_image_concurrent_matches() -> usize {
+    num_cpus::get()
+}
 
-/// When auditing, compare images against banned database.
-///
-/// This is expensive when lots of images are listed as banned.
-// TODO: this seems to leak memory when used a lot, investigate and fix, currently disabled
-pub const AUDIT_IMAGE_COMPARE: bool = true;
+#[derive(Debug, Deserialize, Clone)]
+pub struct File {
+    /// The maximum file size in bytes of files to check for legality.
+    #[serde(default = "default_file_max_size")]
+    pub max_size: i64,
+}
 
-/// Time after which to self-destruct ban notification messages by this bot.
-///
-/// Set to `None` to not self-destruct.
-pub const NOTIFY_SELF_DESTRUCT_TIME: Option<u64> = Some(60);
+impl Default for File {
+    fn default() -> Self {
+        Self {
+            max_size: default_file_max_size(),
+        }
+    }
+}
 
-lazy_static! {
-    /// Number of Telegram API updates to process concurrently.
-    pub static ref TELEGRAM_CONCURRENT_UPDATES: usize = num_cpus::get().max(2);
+fn default_file_max_size() -> i64 {
+    100 * 1024 * 1024
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Address {
+    /// Illegal cryptocurrency addresses, such as scam deposit addresses.
+    ///
+    /// Base58Check, Bech32 and hexadecimal (EIP-55) addresses are supported.
+    #[serde(default)]
+    pub blocklist: Vec<String>,
+
+    /// Giveaway wording, scored along with any cryptocurrency address in the same message text.
+    ///
+    /// As literal text or regex rules, see `Pattern`.
+    #[serde(default)]
+    pub giveaway: Vec<Pattern>,
+
+    /// Normalized addresses from `blocklist`.
+    #[serde(skip)]
+    pub blocked: HashSet<String>,
+
+    /// Matcher compiled from `giveaway`, matching the skeleton of message text.
+    #[serde(skip)]
+    pub giveaway_matcher: Matcher,
+}
+
+impl Address {
+    /// Normalize the blocklist, and compile the giveaway wording into a matcher.
+    ///
+    /// Invalid addresses in the blocklist are skipped.
+    fn compile(&mut self) {
+        self.blocked = self
+            .blocklist
+            .iter()
+            .filter_map(|address| address::Address::parse(address.trim()))
+            .map(|address| address.normalized())
+            .collect();
+        self.giveaway_matcher = Matcher::new(&self.giveaway, skeleton);
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Classifier {
+    /// Classifier model file to load, trained with the `train` command.
+    ///
+    /// The path is relative to the configuration file. The classifier is disabled if not set.
+    #[serde(default)]
+    pub model: Option<String>,
+
+    /// Minimum spam probability for the classifier to yield a verdict.
+    #[serde(default = "default_classifier_threshold")]
+    pub threshold: f64,
+
+    /// The loaded classifier model.
+    #[serde(skip)]
+    pub loaded: Option<Arc<Model>>,
+
+    /// Path the model was loaded from, to watch for changes.
+    #[serde(skip)]
+    pub loaded_path: Option<PathBuf>,
+}
+
+impl Classifier {
+    /// Load the configured classifier model, if any.
+    ///
+    /// The model path is relative to `base`, the directory of the configuration file.
+    pub fn load(&mut self, base: &Path) -> Result<(), Error> {
+        if let Some(model) = &self.model {
+            let path = base.join(model);
+            let model = Model::load(&path).map_err(|err| Error::Model(path.clone(), err))?;
+            self.loaded = Some(Arc::new(model));
+            self.loaded_path = Some(path);
+        }
+        Ok(())
+    }
+}
+
+impl Default for Classifier {
+    fn default() -> Self {
+        Self {
+            model: None,
+            threshold: default_classifier_threshold(),
+            loaded: None,
+            loaded_path: None,
+        }
+    }
+}
+
+fn default_classifier_threshold() -> f64 {
+    0.9
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Scoring {
+    /// Score of a match for each kind of rule.
+    #[serde(default)]
+    pub weights: Weights,
+
+    /// Suspicious phrases in message text, each adding its weight to the score when found.
+    #[serde(default)]
+    pub phrases: Vec<Phrase>,
+
+    /// Minimum scores to take actions at.
+    #[serde(default)]
+    pub thresholds: Thresholds,
+
+    /// Matcher compiled from `phrases`, matching the skeleton of message text.
+    #[serde(skip)]
+    pub matcher: Matcher,
+}
+
+impl Scoring {
+    /// Get the score of the given verdict.
+    pub fn score(&self, verdict: &Verdict) -> f64 {
+        let weights = &self.weights;
+        match &verdict.rule {
+            Rule::Text { .. } => weights.text,
+            Rule::Phrases { score, .. } => *score,
+            Rule::Host { .. } => weights.host,
+            Rule::HostPart { .. } => weights.host_part,
+            Rule::Webpage { .. } => weights.webpage,
+            Rule::Template { .. } => weights.template,
+            Rule::ImageText { .. } => weights.image_text,
+            Rule::Address { .. } => weights.address,
+            Rule::GiveawayAddress { .. } => weights.giveaway_address,
+            Rule::Classifier { probability } => weights.classifier * probability,
+            Rule::Flood { .. } => weights.flood,
+            Rule::NearDuplicate { .. } => weights.near_duplicate,
+            Rule::Combined { verdicts } => verdicts.iter().map(|verdict| self.score(verdict)).sum(),
+        }
+    }
+
+    /// Get the action to take for content with the given verdict.
+    pub fn action(&self, verdict: Option<&Verdict>) -> Action {
+        let score = verdict.map_or(0.0, |verdict| self.score(verdict));
+        self.thresholds.action(score)
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Weights {
+    /// Score of a match in `scanner.text.text`.
+    #[serde(default = "default_weight")]
+    pub text: f64,
+
+    /// Score of a match in `scanner.web.hosts`.
+    #[serde(default = "default_weight")]
+    pub host: f64,
+
+    /// Score of a match in `scanner.web.host_parts`.
+    #[serde(default = "default_weight")]
+    pub host_part: f64,
+
+    /// Score of a match in `scanner.web.text`.
+    #[serde(default = "default_weight")]
+    pub webpage: f64,
+
+    /// Score of an image similar to an illegal image template.
+    #[serde(default = "default_weight")]
+    pub template: f64,
+
+    /// Score of a match in `scanner.image.text`.
+    #[serde(default = "default_weight")]
+    pub image_text: f64,
+
+    /// Score of a match in `scanner.address.blocklist`.
+    #[serde(default = "default_weight")]
+    pub address: f64,
+
+    /// Score of a match in `scanner.address.giveaway`, along with any address.
+    #[serde(default = "default_weight")]
+    pub giveaway_address: f64,
+
+    /// Score of text classified as spam, multiplied by the spam probability.
+    ///
+    /// The probability is below 1, so with the default weight the classifier alone never reaches
+    /// the ban threshold. Set lower thresholds, such as `delete`, for it to take action.
+    #[serde(default = "default_weight")]
+    pub classifier: f64,
+
+    /// Score of text posted in many chats in a short time, see `Fingerprints`.
+    #[serde(default = "default_weights_flood")]
+    pub flood: f64,
+
+    /// Score of text near duplicate to banned spam, see `Archive`.
+    ///
+    /// This is below the ban threshold by default, as banned text is archived in turn.
+    #[serde(default = "default_weights_near_duplicate")]
+    pub near_duplicate: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            text: default_weight(),
+            host: default_weight(),
+            host_part: default_weight(),
+            webpage: default_weight(),
+            template: default_weight(),
+            image_text: default_weight(),
+            address: default_weight(),
+            giveaway_address: default_weight(),
+            classifier: default_weight(),
+            flood: default_weights_flood(),
+            near_duplicate: default_weights_near_duplicate(),
+        }
+    }
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+fn default_weights_flood() -> f64 {
+    0.5
+}
+
+fn default_weights_near_duplicate() -> f64 {
+    0.5
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Phrase {
+    /// The phrase, as literal text or regex rule, see `Pattern`.
+    pub rule: Pattern,
+
+    /// Score to add when the phrase is found.
+    pub weight: f64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Thresholds {
+    /// Minimum score to forward a message to the spam log chat, without taking action.
+    ///
+    /// Weaker signals, such as near duplicates and floods, are flagged at the default.
+    #[serde(default = "default_thresholds_flag")]
+    pub flag: Option<f64>,
+
+    /// Minimum score to delete a message.
+    #[serde(default)]
+    pub delete: Option<f64>,
+
+    /// Minimum score to delete a message, and restrict the user from sending messages.
+    #[serde(default)]
+    pub restrict: Option<f64>,
+
+    /// Minimum score to delete a message, and ban the user.
+    #[serde(default = "default_thresholds_ban")]
+    pub ban: f64,
+}
+
+impl Thresholds {
+    /// Get the action to take for the given score.
+    pub fn action(&self, score: f64) -> Action {
+        let reached = |threshold: Option<f64>| threshold.map_or(false, |t| score >= t);
+        if score >= self.ban {
+            Action::Ban
+        } else if reached(self.restrict) {
+            Action::Restrict
+        } else if reached(self.delete) {
+            Action::Delete
+        } else if reached(self.flag) {
+            Action::Flag
+        } else {
+            Action::Ignore
+        }
+    }
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            flag: default_thresholds_flag(),
+            delete: None,
+            restrict: None,
+            ban: default_thresholds_ban(),
+        }
+    }
+}
+
+fn default_thresholds_flag() -> Option<f64> {
+    Some(0.3)
+}
+
+fn default_thresholds_ban() -> f64 {
+    1.0
+}
+
+/// Action to take for a message, based on its score.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    /// The message is legal.
+    Ignore,
+
+    /// Forward the message to the spam log chat.
+    Flag,
+
+    /// Delete the message.
+    Delete,
+
+    /// Delete the message, and restrict the user from sending messages.
+    Restrict,
+
+    /// Delete the message, and ban the user.
+    Ban,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Feeds {
+    /// Rule feeds to merge into the scanner rules, as local file paths or HTTP(S) URLs.
+    #[serde(default)]
+    pub sources: Vec<String>,
+
+    /// Interval in seconds to fetch rule feeds at.
+    #[serde(default = "default_feeds_interval")]
+    pub interval: u64,
+
+    /// Directory to cache the last good copy of each rule feed in.
+    #[serde(default = "default_feeds_cache")]
+    pub cache: String,
+}
+
+impl Default for Feeds {
+    fn default() -> Self {
+        Self {
+            sources: vec![],
+            interval: default_feeds_interval(),
+            cache: default_feeds_cache(),
+        }
+    }
+}
+
+fn default_feeds_interval() -> u64 {
+    60 * 60
+}
+
+fn default_feeds_cache() -> String {
+    "./feeds".into()
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Fingerprints {
+    /// Time in seconds to remember fingerprints of illegal content for.
+    ///
+    /// Content matching a remembered fingerprint is illegal without auditing it again, in all
+    /// chats. Set to `0` to disable.
+    #[serde(default = "default_fingerprints_ttl")]
+    pub ttl: u64,
+
+    /// Maximum Hamming distance between perceptual hashes of images to consider them equal.
+    #[serde(default = "default_fingerprints_image_distance")]
+    pub image_distance: u32,
+
+    /// Minimum length in characters of message text to fingerprint.
+    ///
+    /// Short texts are posted in many chats by legal users as well.
+    #[serde(default = "default_fingerprints_min_text_length")]
+    pub min_text_length: usize,
+
+    /// Number of different chats the same text must be posted in to raise a flood signal.
+    ///
+    /// Set to `0` to disable.
+    #[serde(default = "default_fingerprints_flood_chats")]
+    pub flood_chats: usize,
+
+    /// Time window in seconds to count chats the same text is posted in.
+    #[serde(default = "default_fingerprints_flood_window")]
+    pub flood_window: u64,
+}
+
+impl Default for Fingerprints {
+    fn default() -> Self {
+        Self {
+            ttl: default_fingerprints_ttl(),
+            image_distance: default_fingerprints_image_distance(),
+            min_text_length: default_fingerprints_min_text_length(),
+            flood_chats: default_fingerprints_flood_chats(),
+            flood_window: default_fingerprints_flood_window(),
+        }
+    }
+}
+
+fn default_fingerprints_ttl() -> u64 {
+    24 * 60 * 60
+}
 
-    /// Number of images to match at the same time.
+fn default_fingerprints_image_distance() -> u32 {
+    4
+}
+
+fn default_fingerprints_min_text_length() -> usize {
+    30
+}
+
+fn default_fingerprints_flood_chats() -> usize {
+    3
+}
+
+fn default_fingerprints_flood_window() -> u64 {
+    10 * 60
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Archive {
+    /// File to persistently store SimHash signatures of banned message text in.
+    ///
+    /// If not set, signatures are lost when the bot restarts.
+    #[serde(default)]
+    pub path: Option<String>,
+
+    /// Maximum Hamming distance between signatures to consider text a near duplicate.
+    ///
+    /// Set to `0` to only match text with the same words.
+    #[serde(default = "default_archive_distance")]
+    pub distance: u32,
+
+    /// Minimum number of words in message text to archive or check.
+    ///
+    /// Signatures of short texts are unreliable.
+    #[serde(default = "default_archive_min_words")]
+    pub min_words: usize,
+
+    /// Maximum number of signatures to keep, the oldest are dropped first.
+    ///
+    /// Set to `0` to disable the archive.
+    #[serde(default = "default_archive_max_size")]
+    pub max_size: usize,
+}
+
+impl Default for Archive {
+    fn default() -> Self {
+        Self {
+            path: None,
+            distance: default_archive_distance(),
+            min_words: default_archive_min_words(),
+            max_size: default_archive_max_size(),
+        }
+    }
+}
+
+fn default_archive_distance() -> u32 {
+    6
+}
+
+fn default_archive_min_words() -> usize {
+    8
+}
+
+fn default_archive_max_size() -> usize {
+    10_000
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct Http {
+    /// Timeout in seconds for requests to audit URLs and webpages.
+    #[serde(default = "default_http_timeout")]
+    pub timeout: u64,
+
+    /// Timeout in seconds to connect to a host.
+    #[serde(default = "default_http_connect_timeout")]
+    pub connect_timeout: u64,
+
+    /// Timeout in seconds for downloading files and rule feeds.
+    #[serde(default = "default_http_download_timeout")]
+    pub download_timeout: u64,
+
+    /// The user agent to send when downloading files and rule feeds.
+    #[serde(default = "default_http_user_agent")]
+    pub user_agent: String,
+
+    /// The user agent to send when auditing URLs and webpages.
+    ///
+    /// This looks like a browser, as spam sites may show harmless content to bots.
+    #[serde(default = "default_http_audit_user_agent")]
+    pub audit_user_agent: String,
+
+    /// Maximum number of concurrent requests to a single host, when auditing URLs and webpages.
     ///
-    /// This is the maximum number of matches to run concurrently for each image against the list
-    /// of banned image templates.
-    pub static ref IMAGE_CONCURRENT_MATCHES: usize = num_cpus::get();
+    /// Downloads, such as Telegram files and rule feeds, are not limited. Set to `0` for no limit.
+    #[serde(default = "default_http_max_per_host")]
+    pub max_per_host: usize,
+}
+
+impl Default for Http {
+    fn default() -> Self {
+        Self {
+            timeout: default_http_timeout(),
+            connect_timeout: default_http_connect_timeout(),
+            download_timeout: default_http_download_timeout(),
+            user_agent: default_http_user_agent(),
+            audit_user_agent: default_http_audit_user_agent(),
+            max_per_host: default_http_max_per_host(),
+        }
+    }
+}
+
+fn default_http_timeout() -> u64 {
+    15
+}
+
+fn default_http_connect_timeout() -> u64 {
+    20
+}
+
+fn default_http_download_timeout() -> u64 {
+    60
+}
+
+fn default_http_user_agent() -> String {
+    concat!("ban-binance-bot/", env!("CARGO_PKG_VERSION")).into()
+}
+
+fn default_http_audit_user_agent() -> String {
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) \
+        Chrome/78.0.3904.108 Safari/537.36"
+        .into()
+}
+
+fn default_http_max_per_host() -> usize {
+    4
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct UrlCache {
+    /// Maximum number of URL audits to cache.
+    ///
+    /// Set to `0` to disable the cache.
+    #[serde(default = "default_url_cache_size")]
+    pub size: usize,
+
+    /// Time in seconds to cache URL audits for.
+    #[serde(default = "default_url_cache_ttl")]
+    pub ttl: u64,
+
+    /// File to persistently store the cache in when the bot stops.
+    ///
+    /// If not set, the cache starts empty each time the bot starts.
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+impl Default for UrlCache {
+    fn default() -> Self {
+        Self {
+            size: default_url_cache_size(),
+            ttl: default_url_cache_ttl(),
+            path: None,
+        }
+    }
+}
+
+fn default_url_cache_size() -> usize {
+    10_000
+}
+
+fn default_url_cache_ttl() -> u64 {
+    60 * 60
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::verdict::Kind;
+
+    #[test]
+    fn test_env_override() {
+        let mut value: Value = toml::from_str("[general]\nnotification_self_destruct = 60").unwrap();
+        set_key(&mut value, "general.notification_self_destruct", parse_value("30")).unwrap();
+        set_key(&mut value, "telegram.token", parse_value("123:abc")).unwrap();
+        set_key(&mut value, "scanner.text.text", parse_value("[\"a\", \"b\"]")).unwrap();
+
+        assert_eq!(value["general"]["notification_self_destruct"].as_integer(), Some(30));
+        assert_eq!(value["telegram"]["token"].as_str(), Some("123:abc"));
+        assert_eq!(value["scanner"]["text"]["text"].as_array().map(|a| a.len()), Some(2));
+        assert!(set_key(&mut value, "general.notification_self_destruct.x", parse_value("1")).is_err());
+    }
+
+    #[test]
+    fn test_include() {
+        let dir = tempfile::tempdir().unwrap();
+        let fixture = fs::read_to_string("./tests/fixtures/config.toml").unwrap();
+        let config = format!("include = [\"extra.toml\", \"rules.d/*.toml\"]\n{}", fixture);
+        fs::write(dir.path().join("config.toml"), config).unwrap();
+        fs::create_dir(dir.path().join("rules.d")).unwrap();
+        fs::write(dir.path().join("extra.toml"), "[web]\nhosts = [\"c.example\"]").unwrap();
+        fs::write(dir.path().join("rules.d/b.toml"), "[web]\nhosts = [\"b.example\"]").unwrap();
+        fs::write(dir.path().join("rules.d/a.toml"), "[web]\nhosts = [\"a.example\"]").unwrap();
+        fs::write(dir.path().join("rules.d/a.toml.bak"), "invalid").unwrap();
+
+        let config = Config::from_path(dir.path().join("config.toml").to_str().unwrap()).unwrap();
+        let hosts = &config.scanner.web.hosts;
+
+        // Configured paths are relative to the configuration file as well
+        let image_dir = config.scanner.image.dir.as_ref().unwrap();
+        assert_eq!(Path::new(image_dir), dir.path().join("../../res/illegal/"));
+        assert_eq!(Path::new(&config.feeds.cache), dir.path().join("./feeds"));
+        assert_eq!(hosts[hosts.len() - 3..], ["c.example", "a.example", "b.example"]);
+        assert_eq!(config.included.len(), 4);
+
+        // A broken rule file fails loading
+        fs::write(dir.path().join("rules.d/c.toml"), "[web]\nhosts = \"d.example\"").unwrap();
+        assert!(match Config::from_path(dir.path().join("config.toml").to_str().unwrap()) {
+            Err(Error::Include(path, _)) => path.ends_with("rules.d/c.toml"),
+            _ => false,
+        });
+
+        // So does a misspelled rule list
+        fs::write(dir.path().join("rules.d/c.toml"), "[web]\nhost = [\"d.example\"]").unwrap();
+        assert!(match Config::from_path(dir.path().join("config.toml").to_str().unwrap()) {
+            Err(Error::Include(path, _)) => path.ends_with("rules.d/c.toml"),
+            _ => false,
+        });
+    }
+
+    #[test]
+    fn test_classifier_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let samples = vec![
+            ("Deposit bonus for the first 5000 participants", true),
+            ("Good morning", false),
+        ];
+        Model::train(2, samples).unwrap().save(dir.path().join("model.json")).unwrap();
+
+        // The model is found relative to the configuration file, not the working directory
+        let mut classifier = Classifier {
+            model: Some("model.json".into()),
+            ..Classifier::default()
+        };
+        classifier.load(dir.path()).unwrap();
+        assert!(classifier.loaded.is_some());
+        assert_eq!(classifier.loaded_path, Some(dir.path().join("model.json")));
+    }
+
+    #[test]
+    fn test_scoring() {
+        let scoring: Scoring = toml::from_str(
+            "[weights]\nhost = 0.6\n[thresholds]\nflag = 0.3\ndelete = 0.5",
+        )
+        .unwrap();
+        let text = Verdict::new(Kind::Text, Rule::Text { needle: "bonus".into() });
+        let host = Verdict::new(Kind::Url, Rule::Host { host: "mxevent.site".into() });
+        let phrases = Verdict::new(Kind::Text, Rule::Phrases { phrases: vec![], score: 0.3 });
+
+        assert_eq!(scoring.action(None), Action::Ignore);
+        assert_eq!(scoring.action(Some(&phrases)), Action::Flag);
+        assert_eq!(scoring.action(Some(&host)), Action::Delete);
+        assert_eq!(scoring.action(Some(&text)), Action::Ban);
+
+        let combined = Verdict::combine(vec![host, phrases]).unwrap();
+        assert!((scoring.score(&combined) - 0.9).abs() < 1e-9);
+        assert_eq!(scoring.action(Some(&combined)), Action::Delete);
+    }
 }
@@ -0,0 +1,137 @@
+use std::fmt;
+
+use super::Config;
+
+/// Per-chat settings.
+///
+/// Each setting overrides the global configuration for a single chat, if set.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChatSettings {
+    /// Post a notification when a user is banned, overrides `general.notify`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notify: Option<bool>,
+
+    /// Self-destruct time of notifications, overrides `general.notification_self_destruct`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub self_destruct: Option<u64>,
+
+    /// Read text from images, overrides `scanner.image.ocr`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ocr: Option<bool>,
+
+    /// Compare images against templates, overrides `scanner.image.compare`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_compare: Option<bool>,
+}
+
+impl ChatSettings {
+    /// Names of all settings.
+    pub const KEYS: [&'static str; 4] = ["notify", "self_destruct", "ocr", "image_compare"];
+
+    /// Check whether no setting is overridden.
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Set the setting with the given key, parsing the given value.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), Error> {
+        match key {
+            "notify" => self.notify = Some(parse_bool(value)?),
+            "self_destruct" => {
+                self.self_destruct = Some(value.parse().map_err(|_| Error::Value(value.into()))?)
+            }
+            "ocr" => self.ocr = Some(parse_bool(value)?),
+            "image_compare" => self.image_compare = Some(parse_bool(value)?),
+            key => return Err(Error::Key(key.into())),
+        }
+        Ok(())
+    }
+
+    /// Reset the setting with the given key, to use the global configuration again.
+    pub fn reset(&mut self, key: &str) -> Result<(), Error> {
+        match key {
+            "notify" => self.notify = None,
+            "self_destruct" => self.self_destruct = None,
+            "ocr" => self.ocr = None,
+            "image_compare" => self.image_compare = None,
+            key => return Err(Error::Key(key.into())),
+        }
+        Ok(())
+    }
+
+    /// Apply these settings to the given global configuration.
+    ///
+    /// Returns the configuration to use for the chat.
+    pub fn apply(&self, config: &Config) -> Config {
+        let mut config = config.clone();
+        if let Some(notify) = self.notify {
+            config.general.notify = notify;
+        }
+        if let Some(self_destruct) = self.self_destruct {
+            config.general.notification_self_destruct = self_destruct;
+        }
+        if let Some(ocr) = self.ocr {
+            config.scanner.image.ocr = ocr;
+        }
+        if let Some(compare) = self.image_compare {
+            config.scanner.image.compare = compare;
+        }
+        config
+    }
+
+    /// Describe the effective settings for a chat, marking settings not overridden.
+    pub fn describe(&self, config: &Config) -> String {
+        let effective = self.apply(config);
+        let line = |key: &str, value: String, set: bool| {
+            format!("{}: {}{}", key, value, if set { "" } else { " (default)" })
+        };
+        vec![
+            line("notify", effective.general.notify.to_string(), self.notify.is_some()),
+            line(
+                "self_destruct",
+                effective.general.notification_self_destruct.to_string(),
+                self.self_destruct.is_some(),
+            ),
+            line("ocr", effective.scanner.image.ocr.to_string(), self.ocr.is_some()),
+            line(
+                "image_compare",
+                effective.scanner.image.compare.to_string(),
+                self.image_compare.is_some(),
+            ),
+        ]
+        .join("\n")
+    }
+}
+
+/// Parse a boolean setting value.
+fn parse_bool(value: &str) -> Result<bool, Error> {
+    match value.to_lowercase().as_str() {
+        "true" | "on" | "yes" | "1" => Ok(true),
+        "false" | "off" | "no" | "0" => Ok(false),
+        _ => Err(Error::Value(value.into())),
+    }
+}
+
+/// A chat setting error.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// Unknown setting key.
+    Key(String),
+
+    /// Invalid value for the setting.
+    Value(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Key(key) => write!(
+                f,
+                "unknown setting '{}', use one of: {}",
+                key,
+                ChatSettings::KEYS.join(", ")
+            ),
+            Error::Value(value) => write!(f, "invalid value '{}'", value),
+        }
+    }
+}
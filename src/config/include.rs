@@ -0,0 +1,130 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{rules::Rules, Config, Error};
+
+/// Merge the rules from all files matching the `include` patterns into the configuration.
+///
+/// Patterns are relative to `base`, the directory of the configuration file. The file name of a
+/// pattern may contain `*` and `?` wildcards, such as `rules.d/*.toml`.
+///
+/// Precedence is: rules in the configuration file itself, then included files in the order of
+/// their patterns, where files matching the same pattern are ordered by name. Rules that are
+/// already present are skipped.
+pub fn apply(config: &mut Config, base: &Path) -> Result<(), Error> {
+    for pattern in config.include.clone() {
+        let pattern = base.join(pattern);
+        let files = resolve(&pattern)?;
+
+        // Remember the directory of a wildcard pattern, to notice added files
+        if is_wildcard(&pattern) {
+            config.included.push(dir(&pattern).into());
+        }
+
+        for file in files {
+            let rules = load(&file).map_err(|err| Error::Include(file.clone(), Box::new(err)))?;
+            rules.merge_into(&mut config.scanner);
+            config.included.push(file);
+        }
+    }
+
+    Ok(())
+}
+
+/// Load rules from the given file.
+fn load(path: &Path) -> Result<Rules, Error> {
+    toml::from_str(&fs::read_to_string(path).map_err(Error::Read)?).map_err(Error::Toml)
+}
+
+/// Resolve the given pattern into a list of files, sorted by name.
+///
+/// A pattern without wildcards resolves to itself.
+fn resolve(pattern: &Path) -> Result<Vec<PathBuf>, Error> {
+    if !is_wildcard(pattern) {
+        return Ok(vec![pattern.into()]);
+    }
+
+    let name = pattern.file_name().and_then(|name| name.to_str()).unwrap_or("");
+    let parent = dir(pattern);
+    let entries = fs::read_dir(parent)
+        .map_err(|err| Error::Include(parent.into(), Box::new(Error::Read(err))))?;
+
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|file| file.to_str())
+                .map_or(false, |file| matches(name, file))
+        })
+        .collect();
+    files.sort();
+
+    Ok(files)
+}
+
+/// Get the directory of the given pattern.
+fn dir(pattern: &Path) -> &Path {
+    match pattern.parent() {
+        Some(dir) if dir != Path::new("") => dir,
+        _ => Path::new("."),
+    }
+}
+
+/// Check whether the file name of the given pattern has wildcards.
+fn is_wildcard(pattern: &Path) -> bool {
+    pattern
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map_or(false, |name| name.contains(|c| c == '*' || c == '?'))
+}
+
+/// Check whether the given name matches the wildcard pattern.
+///
+/// `*` matches any number of characters, `?` matches a single character.
+fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // Backtrack to the last star on mismatch
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(&'*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("*.toml", "hosts.toml"));
+        assert!(matches("*.toml", ".toml"));
+        assert!(matches("rules-?.toml", "rules-1.toml"));
+        assert!(matches("*-*.toml", "a-b-c.toml"));
+        assert!(!matches("*.toml", "hosts.toml.bak"));
+        assert!(!matches("rules-?.toml", "rules-12.toml"));
+    }
+}
@@ -0,0 +1,573 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use url::Url;
+
+use super::{pattern::Pattern, Address, Config, Scoring};
+use crate::{
+    scanner::file::FileType,
+    util::{address, text::skeleton},
+};
+
+/// Minimum length in characters of text needles.
+///
+/// Shorter needles are likely to match legal messages as well.
+pub const MIN_TEXT_LENGTH: usize = 10;
+
+/// Minimum length in characters of host parts.
+///
+/// Shorter host parts are likely to match legal hosts as well.
+pub const MIN_HOST_PART_LENGTH: usize = 6;
+
+/// A configuration issue.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    /// The severity of the issue.
+    pub severity: Severity,
+
+    /// Path of the configuration key the issue is about, such as `scanner.web.hosts[2]`.
+    pub key: String,
+
+    /// Description of the issue.
+    pub message: String,
+}
+
+impl Issue {
+    fn error<K: Into<String>, M: Into<String>>(key: K, message: M) -> Self {
+        Self {
+            severity: Severity::Error,
+            key: key.into(),
+            message: message.into(),
+        }
+    }
+
+    fn warning<K: Into<String>, M: Into<String>>(key: K, message: M) -> Self {
+        Self {
+            severity: Severity::Warning,
+            key: key.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.severity, self.key, self.message)
+    }
+}
+
+/// Severity of a configuration issue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The configuration is invalid, and must not be used.
+    Error,
+
+    /// The configuration works, but probably not as intended.
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// Lint the given configuration, and report all issues that are found.
+pub fn lint(config: &Config) -> Vec<Issue> {
+    let mut issues = vec![];
+
+    // Text needles
+    let scanner = &config.scanner;
+    let lowercase = |text: &str| text.to_lowercase();
+    lint_needles(&mut issues, "scanner.text.text", &scanner.text.text, skeleton);
+    lint_needles(&mut issues, "scanner.web.text", &scanner.web.text, lowercase);
+    let image_text: Vec<Pattern> =
+        scanner.image.text.iter().map(|text| Pattern::Literal(text.clone())).collect();
+    lint_needles(&mut issues, "scanner.image.text", &image_text, lowercase);
+
+    // URL hosts
+    lint_hosts(&mut issues, config);
+
+    // Literal rules written like the old regex syntax
+    let rules = [
+        ("scanner.text.text", &scanner.text.text),
+        ("scanner.web.text", &scanner.web.text),
+        ("scanner.web.hosts", &scanner.web.hosts),
+        ("scanner.web.host_parts", &scanner.web.host_parts),
+        ("scanner.address.giveaway", &scanner.address.giveaway),
+    ];
+    for (key, rules) in rules.iter() {
+        lint_slashes(&mut issues, key, rules);
+    }
+
+    // Cryptocurrency addresses
+    lint_addresses(&mut issues, &scanner.address);
+
+    // Classifier
+    let threshold = scanner.classifier.threshold;
+    if !(0.0..=1.0).contains(&threshold) {
+        issues.push(Issue::error(
+            "scanner.classifier.threshold",
+            format!("must be between 0 and 1, is {}", threshold),
+        ));
+    }
+
+    // Scoring weights, phrases and thresholds
+    lint_scoring(&mut issues, &scanner.scoring);
+
+    // Image templates
+    if let Some(dir) = &scanner.image.dir {
+        lint_image_dir(&mut issues, Path::new(dir));
+    }
+    let threshold = scanner.image.threshold;
+    if !(0.0..=1.0).contains(&threshold) {
+        issues.push(Issue::error(
+            "scanner.image.threshold",
+            format!("must be between 0 and 1, is {}", threshold),
+        ));
+    }
+
+    // Spam archive, signatures of unrelated text differ in about half of their 64 bits
+    let distance = config.archive.distance;
+    if distance >= 32 {
+        issues.push(Issue::error(
+            "archive.distance",
+            format!("must be less than 32, is {}", distance),
+        ));
+    } else if distance > 12 {
+        issues.push(Issue::warning(
+            "archive.distance",
+            format!("{} likely matches unrelated text", distance),
+        ));
+    }
+
+    // HTTP clients
+    let http = &config.http;
+    for (name, user_agent) in &[
+        ("user_agent", &http.user_agent),
+        ("audit_user_agent", &http.audit_user_agent),
+    ] {
+        if user_agent.chars().any(|c| !(' '..='~').contains(&c)) {
+            issues.push(Issue::error(
+                format!("http.{}", name),
+                "must only contain printable ASCII characters",
+            ));
+        }
+    }
+    for (name, timeout) in &[
+        ("timeout", http.timeout),
+        ("connect_timeout", http.connect_timeout),
+        ("download_timeout", http.download_timeout),
+    ] {
+        if *timeout == 0 {
+            issues.push(Issue::error(format!("http.{}", name), "must be more than 0"));
+        }
+    }
+
+    if let Some(webhook) = &config.telegram.webhook {
+        if let Err(err) = Url::parse(&webhook.url) {
+            issues.push(Issue::error("telegram.webhook.url", format!("invalid URL: {}", err)));
+        }
+        match &webhook.secret {
+            None => issues.push(Issue::error(
+                "telegram.webhook.secret",
+                "missing, anyone reaching the listener could send forged updates",
+            )),
+            Some(secret)
+                if secret.is_empty()
+                    || secret.len() > 256
+                    || !secret
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') =>
+            {
+                issues.push(Issue::error(
+                    "telegram.webhook.secret",
+                    "must be 1 to 256 characters of A-Z, a-z, 0-9, _ and -",
+                ))
+            }
+            Some(_) => {}
+        }
+    }
+
+    issues
+}
+
+/// Lint the address blocklist and giveaway wording.
+fn lint_addresses(issues: &mut Vec<Issue>, config: &Address) {
+    let mut seen: Vec<String> = vec![];
+    for (i, entry) in config.blocklist.iter().enumerate() {
+        let key = format!("scanner.address.blocklist[{}]", i);
+        let normalized = match address::Address::parse(entry.trim()) {
+            Some(address) => address.normalized(),
+            None => {
+                issues.push(Issue::error(
+                    key,
+                    format!("{:?} is not an address, or has an invalid checksum", entry),
+                ));
+                continue;
+            }
+        };
+        if let Some(first) = seen.iter().position(|other| other == &normalized) {
+            issues.push(Issue::warning(&key, format!("{:?} duplicates entry {}", entry, first)));
+        }
+        seen.push(normalized);
+    }
+
+    lint_needles(issues, "scanner.address.giveaway", &config.giveaway, skeleton);
+}
+
+/// Lint the scoring weights, phrases and thresholds.
+fn lint_scoring(issues: &mut Vec<Issue>, scoring: &Scoring) {
+    let weights = &scoring.weights;
+    for (name, weight) in &[
+        ("text", weights.text),
+        ("host", weights.host),
+        ("host_part", weights.host_part),
+        ("webpage", weights.webpage),
+        ("template", weights.template),
+        ("image_text", weights.image_text),
+        ("address", weights.address),
+        ("giveaway_address", weights.giveaway_address),
+        ("classifier", weights.classifier),
+        ("flood", weights.flood),
+        ("near_duplicate", weights.near_duplicate),
+    ] {
+        if *weight < 0.0 {
+            issues.push(Issue::error(
+                format!("scanner.scoring.weights.{}", name),
+                format!("must not be negative, is {}", weight),
+            ));
+        }
+    }
+
+    let rules: Vec<Pattern> = scoring.phrases.iter().map(|phrase| phrase.rule.clone()).collect();
+    lint_needles(issues, "scanner.scoring.phrases", &rules, skeleton);
+    for (i, phrase) in scoring.phrases.iter().enumerate() {
+        if phrase.weight <= 0.0 {
+            issues.push(Issue::error(
+                format!("scanner.scoring.phrases[{}].weight", i),
+                format!("must be above 0, is {}", phrase.weight),
+            ));
+        }
+    }
+
+    // Thresholds must be above 0, and must not exceed the thresholds of heavier actions
+    let thresholds = &scoring.thresholds;
+    let thresholds = [
+        ("flag", thresholds.flag),
+        ("delete", thresholds.delete),
+        ("restrict", thresholds.restrict),
+        ("ban", Some(thresholds.ban)),
+    ];
+    for (i, (name, threshold)) in thresholds.iter().enumerate() {
+        let threshold = match threshold {
+            Some(threshold) => *threshold,
+            None => continue,
+        };
+        let key = format!("scanner.scoring.thresholds.{}", name);
+        if threshold <= 0.0 {
+            issues.push(Issue::error(key, format!("must be above 0, is {}", threshold)));
+            continue;
+        }
+        let heavier = thresholds[i + 1..]
+            .iter()
+            .filter_map(|(name, other)| other.map(|other| (name, other)))
+            .find(|(_, other)| *other < threshold);
+        if let Some((other, _)) = heavier {
+            issues.push(Issue::error(
+                key,
+                format!("must not exceed the {} threshold, is {}", other, threshold),
+            ));
+        }
+    }
+}
+
+/// Lint a list of text needles.
+///
+/// Needles that are equal after the given normalization are duplicates.
+fn lint_needles(
+    issues: &mut Vec<Issue>,
+    key: &str,
+    needles: &[Pattern],
+    normalize: fn(&str) -> String,
+) {
+    for (i, needle) in needles.iter().enumerate() {
+        let key = format!("{}[{}]", key, i);
+
+        if needle.source().trim().is_empty() {
+            issues.push(Issue::error(key, "empty entry"));
+            continue;
+        }
+        if needle.is_regex() {
+            lint_regex(issues, &key, needle);
+            continue;
+        }
+        let needle = needle.as_str();
+
+        if needle.chars().count() < MIN_TEXT_LENGTH {
+            issues.push(Issue::warning(
+                &key,
+                format!(
+                    "{:?} is shorter than {} characters, and may match legal messages",
+                    needle, MIN_TEXT_LENGTH,
+                ),
+            ));
+        }
+        if let Some(first) = needles[..i]
+            .iter()
+            .position(|other| normalize(other.as_str()) == normalize(needle))
+        {
+            issues.push(Issue::warning(&key, format!("{:?} duplicates entry {}", needle, first)));
+        }
+    }
+}
+
+/// Lint the URL hosts and host parts.
+fn lint_hosts(issues: &mut Vec<Issue>, config: &Config) {
+    let web = &config.scanner.web;
+
+    for (i, part) in web.host_parts.iter().enumerate() {
+        let key = format!("scanner.web.host_parts[{}]", i);
+
+        if part.source().trim().is_empty() {
+            issues.push(Issue::error(key, "empty entry, matches every URL"));
+            continue;
+        }
+        if part.is_regex() {
+            lint_regex(issues, &key, part);
+            continue;
+        }
+        let part = part.as_str();
+        lint_host_case(issues, &key, part);
+        if part.chars().count() < MIN_HOST_PART_LENGTH {
+            issues.push(Issue::warning(
+                &key,
+                format!(
+                    "{:?} is shorter than {} characters, and may match legal hosts",
+                    part, MIN_HOST_PART_LENGTH,
+                ),
+            ));
+        }
+
+        // A host part containing an earlier host part is never reached
+        let shadowed = web.host_parts[..i]
+            .iter()
+            .position(|other| is_literal(other) && part.contains(other.as_str()));
+        if let Some(other) = shadowed {
+            issues.push(Issue::warning(
+                &key,
+                format!("{:?} is already covered by scanner.web.host_parts[{}]", part, other),
+            ));
+        }
+    }
+
+    for (i, host) in web.hosts.iter().enumerate() {
+        let key = format!("scanner.web.hosts[{}]", i);
+
+        if host.source().trim().is_empty() {
+            issues.push(Issue::error(key, "empty entry"));
+            continue;
+        }
+        if host.is_regex() {
+            lint_regex(issues, &key, host);
+            continue;
+        }
+        let host = host.as_str();
+        lint_host_case(issues, &key, host);
+
+        if let Some(first) = web.hosts[..i].iter().position(|other| other == &host) {
+            issues.push(Issue::warning(&key, format!("{:?} duplicates entry {}", host, first)));
+            continue;
+        }
+        let shadowed = web
+            .host_parts
+            .iter()
+            .position(|part| is_literal(part) && host.contains(part.as_str()));
+        if let Some(part) = shadowed {
+            issues.push(Issue::warning(
+                &key,
+                format!("{:?} is already covered by scanner.web.host_parts[{}]", host, part),
+            ));
+        }
+    }
+}
+
+/// Lint a regex rule, it must not match empty text.
+fn lint_regex(issues: &mut Vec<Issue>, key: &str, pattern: &Pattern) {
+    if let Pattern::Regex { regex, .. } = pattern {
+        if regex.is_match("") {
+            issues.push(Issue::error(
+                key,
+                format!("{:?} matches empty text, and would match everything", pattern.as_str()),
+            ));
+        }
+    }
+}
+
+/// Literal rules shaped like `/pattern/flags` are matched literally, but may be meant as regex.
+///
+/// Regular expressions used to be written this way, they are now written as a table.
+fn lint_slashes(issues: &mut Vec<Issue>, key: &str, rules: &[Pattern]) {
+    for (i, rule) in rules.iter().enumerate() {
+        let text = match rule {
+            Pattern::Literal(text) => text,
+            Pattern::Regex { .. } => continue,
+        };
+        let end = match text.rfind('/') {
+            Some(end) if text.starts_with('/') && end > 1 => end,
+            _ => continue,
+        };
+        if text[end + 1..].chars().all(|flag| "imsxU".contains(flag)) {
+            issues.push(Issue::warning(
+                format!("{}[{}]", key, i),
+                format!(
+                    "{:?} is matched literally, write a regular expression as {{ regex = {:?} }}",
+                    text,
+                    &text[1..end],
+                ),
+            ));
+        }
+    }
+}
+
+/// Check whether the given pattern is a non-empty literal.
+fn is_literal(pattern: &Pattern) -> bool {
+    !pattern.is_regex() && !pattern.as_str().is_empty()
+}
+
+/// Hosts are lowercased before matching, so entries with uppercase characters never match.
+fn lint_host_case(issues: &mut Vec<Issue>, key: &str, host: &str) {
+    if host != host.to_lowercase() {
+        issues.push(Issue::warning(
+            key,
+            format!("{:?} contains uppercase characters, which are never matched", host),
+        ));
+    }
+}
+
+/// Lint the image template directory, all images must be loadable.
+///
+/// Other files are ignored when matching, so these only raise a warning.
+fn lint_image_dir(issues: &mut Vec<Issue>, dir: &Path) {
+    let key = "scanner.image.dir";
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            issues.push(Issue::error(key, format!("can't read '{}': {}", dir.display(), err)));
+            return;
+        }
+    };
+
+    let mut templates = 0;
+    for entry in entries {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(err) => {
+                issues.push(Issue::error(key, format!("can't list template: {}", err)));
+                continue;
+            }
+        };
+        let name = path.file_name().and_then(|name| name.to_str()).unwrap_or("");
+
+        if FileType::from_name(name) != FileType::Image {
+            issues.push(Issue::warning(
+                key,
+                format!("'{}' is not an image, it's ignored", path.display()),
+            ));
+            continue;
+        }
+        if let Err(err) = image::open(&path) {
+            issues.push(Issue::error(
+                key,
+                format!("can't load image '{}': {}", path.display(), err),
+            ));
+            continue;
+        }
+        templates += 1;
+    }
+
+    if templates == 0 {
+        issues.push(Issue::warning(
+            key,
+            format!("'{}' has no template images", dir.display()),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [general]
+            [scanner.text]
+            text = ["First 5000 Participants Bonus", "first 5000 participants bonus", "Bonus", "Fіrst 5000 Pаrticipants Bonus"]
+            [scanner.web]
+            hosts = ["binance.event.site", "mxevent.site", "mxevent.site", "Example.com"]
+            host_parts = ["binance.event", "binance.event2019", "bnb", { regex = '^x*$' }]
+            text = [{ regex = 'b[i1]nance\s+event', flags = "i" }, "/wp-admin/"]
+            [scanner.image]
+            threshold = 0.5
+            text = []
+            [scanner.address]
+            blocklist = ["1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNb", "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed", "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"]
+            giveaway = ["Giveaway", "BTC giveaway event"]
+            [scanner.scoring.thresholds]
+            delete = 0.5
+            restrict = 1.5
+            [[scanner.scoring.phrases]]
+            rule = "Event ends today!"
+            weight = 0.5
+            [[scanner.scoring.phrases]]
+            rule = "Event ends today!"
+            weight = 0.0
+            "#,
+        )
+        .unwrap();
+
+        let keys = |issues: Vec<Issue>| {
+            issues
+                .into_iter()
+                .map(|issue| (issue.severity, issue.key))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            keys(lint(&config)),
+            vec![
+                (Severity::Warning, "scanner.text.text[1]".into()),
+                (Severity::Warning, "scanner.text.text[2]".into()),
+                (Severity::Warning, "scanner.text.text[3]".into()),
+                (Severity::Warning, "scanner.web.host_parts[1]".into()),
+                (Severity::Warning, "scanner.web.host_parts[2]".into()),
+                (Severity::Error, "scanner.web.host_parts[3]".into()),
+                (Severity::Warning, "scanner.web.hosts[0]".into()),
+                (Severity::Warning, "scanner.web.hosts[2]".into()),
+                (Severity::Warning, "scanner.web.hosts[3]".into()),
+                (Severity::Warning, "scanner.web.text[1]".into()),
+                (Severity::Error, "scanner.address.blocklist[0]".into()),
+                (Severity::Warning, "scanner.address.blocklist[2]".into()),
+                (Severity::Warning, "scanner.address.giveaway[0]".into()),
+                (Severity::Warning, "scanner.scoring.phrases[1]".into()),
+                (Severity::Error, "scanner.scoring.phrases[1].weight".into()),
+                (Severity::Error, "scanner.scoring.thresholds.restrict".into()),
+            ],
+        );
+
+        config.scanner.image.threshold = 2.0;
+        config.scanner.image.dir = Some("./src".into());
+        let issues: Vec<_> = lint(&config)
+            .into_iter()
+            .map(|issue| (issue.severity, issue.key))
+            .collect();
+        assert!(issues.contains(&(Severity::Warning, "scanner.image.dir".into())));
+        assert!(!issues.contains(&(Severity::Error, "scanner.image.dir".into())));
+        assert!(issues.contains(&(Severity::Error, "scanner.image.threshold".into())));
+    }
+}
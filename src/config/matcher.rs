@@ -0,0 +1,160 @@
+use std::sync::Arc;
+
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder, MatchKind};
+use regex::Regex;
+
+use super::pattern::Pattern;
+
+/// A list of rules compiled to match them all at once.
+///
+/// Literal rules are compiled into a single Aho-Corasick automaton, so matching takes time
+/// linear to the haystack, no matter how many rules there are. Regex rules are matched after
+/// the literal rules. Matches are reported by their index in the rule list.
+///
+/// A matcher is compiled from a rule list once, and must be compiled again when the list changes.
+/// See `Scanner::compile`.
+#[derive(Debug, Clone, Default)]
+pub struct Matcher {
+    /// Automaton for all literal rules, `None` if there are none.
+    automaton: Option<Arc<AhoCorasick>>,
+
+    /// Index in the rule list, for each pattern in the automaton.
+    literals: Vec<usize>,
+
+    /// Index in the rule list, and the regex, for each regex rule.
+    regexes: Vec<(usize, Regex)>,
+}
+
+impl Matcher {
+    /// Compile the given rule list.
+    ///
+    /// Literal rules are normalized with the given function, which must be the normalization the
+    /// haystacks get. Rules that are empty after normalization are skipped.
+    pub fn new<'a, I>(rules: I, normalize: fn(&str) -> String) -> Self
+    where
+        I: IntoIterator<Item = &'a Pattern>,
+    {
+        let mut literals = vec![];
+        let mut patterns = vec![];
+        let mut regexes = vec![];
+        for (i, rule) in rules.into_iter().enumerate() {
+            match rule {
+                Pattern::Literal(text) => {
+                    let pattern = normalize(text);
+                    if !pattern.is_empty() {
+                        literals.push(i);
+                        patterns.push(pattern);
+                    }
+                }
+                Pattern::Regex { regex, .. } => regexes.push((i, regex.clone())),
+            }
+        }
+
+        // Prefer the earliest match, then the earliest rule
+        let automaton = if patterns.is_empty() {
+            None
+        } else {
+            Some(Arc::new(
+                AhoCorasickBuilder::new()
+                    .match_kind(MatchKind::LeftmostFirst)
+                    .build(&patterns),
+            ))
+        };
+
+        Self {
+            automaton,
+            literals,
+            regexes,
+        }
+    }
+
+    /// Find the index of a rule that matches the given haystack.
+    ///
+    /// Of the literal rules, the one matching earliest in the haystack is returned. Regex rules
+    /// match against the haystack as text.
+    pub fn find(&self, haystack: &[u8]) -> Option<usize> {
+        if let Some(automaton) = &self.automaton {
+            if let Some(found) = automaton.find(haystack) {
+                return Some(self.literals[found.pattern()]);
+            }
+        }
+
+        if self.regexes.is_empty() {
+            return None;
+        }
+        let text = String::from_utf8_lossy(haystack);
+        self.regexes
+            .iter()
+            .find(|(_, regex)| regex.is_match(&text))
+            .map(|(i, _)| *i)
+    }
+
+    /// Find the indices of all rules that match the given haystack, in rule order.
+    ///
+    /// Literal rules that overlap with an earlier literal match in the haystack are not reported.
+    pub fn find_all(&self, haystack: &[u8]) -> Vec<usize> {
+        let mut found: Vec<usize> = match &self.automaton {
+            Some(automaton) => automaton
+                .find_iter(haystack)
+                .map(|found| self.literals[found.pattern()])
+                .collect(),
+            None => vec![],
+        };
+
+        if !self.regexes.is_empty() {
+            let text = String::from_utf8_lossy(haystack);
+            found.extend(
+                self.regexes
+                    .iter()
+                    .filter(|(_, regex)| regex.is_match(&text))
+                    .map(|(i, _)| *i),
+            );
+        }
+
+        found.sort();
+        found.dedup();
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find() {
+        let rules = vec![
+            Pattern::literal("Bonus"),
+            Pattern::literal(""),
+            Pattern::literal("first 5000"),
+            Pattern::regex("5000 (users|participants)", "").unwrap(),
+            Pattern::literal("5000"),
+        ];
+        let matcher = Matcher::new(&rules, |text: &str| text.to_lowercase());
+
+        let find = |haystack: &str| matcher.find(haystack.as_bytes());
+        assert_eq!(find("only the first 5000 users get a bonus"), Some(2));
+        assert_eq!(find("only 5000 users"), Some(4));
+        assert_eq!(find("5000 participants"), Some(4));
+        assert_eq!(find("participants"), None);
+        assert_eq!(Matcher::default().find(b"bonus"), None);
+
+        let rules = vec![Pattern::regex("5000 (users|participants)", "").unwrap()];
+        let matcher = Matcher::new(&rules, |text: &str| text.to_owned());
+        assert_eq!(matcher.find(b"5000 users"), Some(0));
+    }
+
+    #[test]
+    fn test_find_all() {
+        let rules = vec![
+            Pattern::literal("event ends today"),
+            Pattern::regex("5000 (users|participants)", "").unwrap(),
+            Pattern::literal("bonus"),
+        ];
+        let matcher = Matcher::new(&rules, |text: &str| text.to_owned());
+
+        assert_eq!(matcher.find_all(b"bonus for 5000 users, event ends today"), vec![0, 1, 2]);
+        assert_eq!(matcher.find_all(b"bonus bonus"), vec![2]);
+        assert!(matcher.find_all(b"good morning").is_empty());
+    }
+}
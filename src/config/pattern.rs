@@ -0,0 +1,151 @@
+use std::fmt;
+
+use regex::{Regex, RegexBuilder};
+use serde::de::{self, Deserializer};
+
+/// Flags that may be given to regex rules.
+const REGEX_FLAGS: &str = "imsxU";
+
+/// A rule to match, either literal text or a regular expression.
+///
+/// Literal rules are written as a string. Regular expressions are written as a table, such as
+/// `{ regex = 'b[i1]nance\s+us' }`, with optional `flags`. Supported flags are `i`, `m`, `s`, `x`
+/// and `U`, see the `regex` crate. Haystacks are lowercased before matching, so regular
+/// expressions always match case-insensitively, like literal rules.
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    /// Literal text.
+    Literal(String),
+
+    /// A regular expression, along with the pattern it was compiled from.
+    ///
+    /// The rule is the pattern written as `/pattern/flags`, to show in verdicts and lint issues.
+    Regex {
+        rule: String,
+        pattern: String,
+        regex: Regex,
+    },
+}
+
+impl Pattern {
+    /// Build a literal rule.
+    pub fn literal<S: Into<String>>(text: S) -> Self {
+        Pattern::Literal(text.into())
+    }
+
+    /// Compile a regular expression rule, with the given flags.
+    pub fn regex(pattern: &str, flags: &str) -> Result<Self, regex::Error> {
+        if let Some(flag) = flags.chars().find(|flag| !REGEX_FLAGS.contains(*flag)) {
+            return Err(regex::Error::Syntax(format!("unsupported flag {:?}", flag)));
+        }
+
+        let regex = if flags.is_empty() {
+            RegexBuilder::new(pattern)
+        } else {
+            RegexBuilder::new(&format!("(?{}){}", flags, pattern))
+        }
+        .case_insensitive(true)
+        .build()?;
+        Ok(Pattern::Regex {
+            rule: format!("/{}/{}", pattern, flags),
+            pattern: pattern.into(),
+            regex,
+        })
+    }
+
+    /// Get the literal text, or the regular expression written as `/pattern/flags`.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Pattern::Literal(text) => text,
+            Pattern::Regex { rule, .. } => rule,
+        }
+    }
+
+    /// Get the literal text, or the regular expression without flags.
+    pub fn source(&self) -> &str {
+        match self {
+            Pattern::Literal(text) => text,
+            Pattern::Regex { pattern, .. } => pattern,
+        }
+    }
+
+    /// Check whether this is a regular expression.
+    pub fn is_regex(&self) -> bool {
+        match self {
+            Pattern::Literal(_) => false,
+            Pattern::Regex { .. } => true,
+        }
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.is_regex() == other.is_regex() && self.as_str() == other.as_str()
+    }
+}
+
+impl<'a> PartialEq<&'a str> for Pattern {
+    fn eq(&self, other: &&'a str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// A rule as written in the configuration.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawPattern {
+    Literal(String),
+    Regex {
+        regex: String,
+        #[serde(default)]
+        flags: String,
+    },
+}
+
+impl<'de> serde::Deserialize<'de> for Pattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match <RawPattern as serde::Deserialize>::deserialize(deserializer)? {
+            RawPattern::Literal(text) => Ok(Pattern::Literal(text)),
+            RawPattern::Regex { regex, flags } => Pattern::regex(&regex, &flags).map_err(|err| {
+                de::Error::custom(format!("invalid regex rule {:?}: {}", regex, err))
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::rules::TextRules;
+
+    #[test]
+    fn test_parse() {
+        let rules: TextRules = toml::from_str(
+            r#"text = ["Binance US", "/wp-admin/", { regex = 'b[i1]nance\s+us' }, { regex = '^bnb$', flags = "m" }]"#,
+        )
+        .unwrap();
+        assert!(!rules.text[0].is_regex());
+        assert!(!rules.text[1].is_regex());
+        assert_eq!(rules.text[1].as_str(), "/wp-admin/");
+
+        // Regular expressions always match case-insensitively
+        let pattern = &rules.text[2];
+        assert_eq!(pattern.source(), r"b[i1]nance\s+us");
+        assert_eq!(pattern.as_str(), r"/b[i1]nance\s+us/");
+        match pattern {
+            Pattern::Regex { regex, .. } => assert!(regex.is_match("B1NANCE US")),
+            _ => panic!("not a regex"),
+        }
+        assert_eq!(rules.text[3].as_str(), "/^bnb$/m");
+
+        assert!(Pattern::regex("b[i1nance", "").is_err());
+        assert!(Pattern::regex("binance", "g").is_err());
+        assert!(toml::from_str::<TextRules>("text = [{ regex = '(' }]").is_err());
+    }
+}
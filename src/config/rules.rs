@@ -0,0 +1,66 @@
+use super::{pattern::Pattern, Scanner};
+
+/// A set of scanner rules, to merge into the scanner configuration.
+///
+/// This holds the rule lists of the `Text`, `Web`, `Image` and `Address` scanner sections. All
+/// lists are optional, unknown sections and lists are rejected so misspelled rules fail loudly.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Rules {
+    pub text: TextRules,
+    pub web: WebRules,
+    pub image: ImageRules,
+    pub address: AddressRules,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TextRules {
+    pub text: Vec<Pattern>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebRules {
+    pub hosts: Vec<Pattern>,
+    pub host_parts: Vec<Pattern>,
+    pub text: Vec<Pattern>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImageRules {
+    pub text: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AddressRules {
+    pub blocklist: Vec<String>,
+    pub giveaway: Vec<Pattern>,
+}
+
+impl Rules {
+    /// Merge these rules into the given scanner configuration.
+    ///
+    /// Rules are appended after the existing rules. Rules that are already present are skipped.
+    /// Compile the scanner rules again after merging, see `Scanner::compile`.
+    pub fn merge_into(&self, scanner: &mut Scanner) {
+        extend_unique(&mut scanner.text.text, &self.text.text);
+        extend_unique(&mut scanner.web.hosts, &self.web.hosts);
+        extend_unique(&mut scanner.web.host_parts, &self.web.host_parts);
+        extend_unique(&mut scanner.web.text, &self.web.text);
+        extend_unique(&mut scanner.image.text, &self.image.text);
+        extend_unique(&mut scanner.address.blocklist, &self.address.blocklist);
+        extend_unique(&mut scanner.address.giveaway, &self.address.giveaway);
+    }
+}
+
+/// Append the given items to the list, skipping items that are already present.
+fn extend_unique<T: Clone + PartialEq>(list: &mut Vec<T>, items: &[T]) {
+    for item in items {
+        if !list.contains(item) {
+            list.push(item.clone());
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! A corpus of sample messages with expected verdicts.
+//!
+//! Each sample is a TOML file in a corpus directory, such as:
+//!
+//! ```toml
+//! expect = "illegal"
+//! text = "Click here"
+//! entities = [{ type = "text_link", url = "https://exchange.bnblaunch.com/" }]
+//! media = ["../../res/illegal/binance.jpg"]
+//! ```
+//!
+//! Media paths are relative to the directory the sample file is in.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use url::Url;
+
+use crate::scanner::message::Message;
+
+/// A sample message from a corpus.
+#[derive(Debug, Deserialize)]
+pub struct Sample {
+    /// Path of the sample file.
+    #[serde(skip)]
+    pub path: PathBuf,
+
+    /// The expected verdict.
+    pub expect: Expect,
+
+    /// The message text or caption.
+    #[serde(default)]
+    pub text: Option<String>,
+
+    /// Message text entities.
+    #[serde(default)]
+    pub entities: Vec<Entity>,
+
+    /// Attached media files.
+    #[serde(default)]
+    pub media: Vec<PathBuf>,
+}
+
+impl Sample {
+    /// Load a sample from the given file.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let data = fs::read_to_string(path).map_err(|err| Error::Read(path.into(), err))?;
+        let mut sample: Sample =
+            toml::from_str(&data).map_err(|err| Error::Toml(path.into(), err))?;
+        sample.path = path.into();
+        Ok(sample)
+    }
+
+    /// Get the name of this sample, based on its file name.
+    pub fn name(&self) -> String {
+        self.path
+            .file_stem()
+            .and_then(|name| name.to_str())
+            .unwrap_or("?")
+            .into()
+    }
+
+    /// Build a message to audit from this sample.
+    pub fn message(&self) -> Message {
+        let dir = self.path.parent().unwrap_or_else(|| Path::new("."));
+
+        Message {
+            text: self.text.clone(),
+            hidden_urls: self
+                .entities
+                .iter()
+                .filter_map(|entity| match entity {
+                    Entity::TextLink { url } => Url::parse(url).ok(),
+                    Entity::Other => None,
+                })
+                .collect(),
+            files: self.media.iter().map(|path| dir.join(path)).collect(),
+        }
+    }
+}
+
+/// An expected verdict.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Expect {
+    /// The message should be audited as illegal.
+    Illegal,
+
+    /// The message should be audited as legal.
+    Legal,
+}
+
+/// A message text entity.
+///
+/// Matches the Telegram Bot API entity format, only entities relevant for auditing are kept.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Entity {
+    /// A text link, hiding a URL behind text.
+    TextLink { url: String },
+
+    /// Any other entity.
+    #[serde(other)]
+    Other,
+}
+
+/// Load all samples from the given corpus directory.
+///
+/// Samples are sorted by file name.
+pub fn load_dir<P: AsRef<Path>>(dir: P) -> Result<Vec<Sample>, Error> {
+    let dir = dir.as_ref();
+    let mut paths = fs::read_dir(dir)
+        .and_then(|entries| entries.map(|entry| entry.map(|e| e.path())).collect::<Result<Vec<_>, _>>())
+        .map_err(|err| Error::Read(dir.into(), err))?;
+    paths.retain(|path| path.extension().map(|ext| ext == "toml").unwrap_or(false));
+    paths.sort();
+
+    paths.into_iter().map(Sample::from_path).collect()
+}
+
+/// A corpus loading error.
+#[derive(Debug)]
+pub enum Error {
+    /// Failed to read a sample or corpus directory from disk.
+    Read(PathBuf, std::io::Error),
+
+    /// Sample TOML format error.
+    Toml(PathBuf, toml::de::Error),
+}
@@ -0,0 +1,339 @@
+//! Rule feeds.
+//!
+//! A rule feed is a TOML file with scanner rules, hosted at an HTTP(S) URL or stored locally.
+//! Feeds are merged into the scanner rules, so rules can be shared between many instances.
+//!
+//! ```toml
+//! version = 1
+//! templates = ["binance.jpg"]
+//!
+//! [text]
+//! text = ["First 5000 Participants Bonus"]
+//!
+//! [web]
+//! hosts = ["binance.jerseyonline.icu"]
+//! host_parts = ["binance.event"]
+//! text = []
+//!
+//! [image]
+//! text = ["EVENT ENDS AT MIDNIGHT TODAY"]
+//!
+//! [address]
+//! blocklist = []
+//! giveaway = []
+//! ```
+//!
+//! Image templates are given as URLs or paths relative to the feed.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::{
+    config::{rules::Rules, Scanner},
+    util::http::Clients,
+};
+
+/// The supported feed format version.
+pub const VERSION: u32 = 1;
+
+/// A rule feed.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Feed {
+    /// The feed format version.
+    pub version: u32,
+
+    /// Rules to merge into the scanner configuration.
+    #[serde(flatten)]
+    pub rules: Rules,
+
+    /// Illegal image templates, as URLs or paths relative to the feed.
+    #[serde(default)]
+    pub templates: Vec<String>,
+
+    /// Directory the image templates of this feed are stored in, once fetched.
+    #[serde(skip)]
+    pub template_dir: Option<PathBuf>,
+}
+
+impl Feed {
+    /// Parse a feed, and check whether its format version is supported.
+    pub fn parse(data: &str) -> Result<Self, Error> {
+        let feed: Feed = toml::from_str(data).map_err(Error::Toml)?;
+        if feed.version != VERSION {
+            return Err(Error::Version(feed.version));
+        }
+
+        // Empty rules are a mistake, and may match everything
+        let rules = &feed.rules;
+        let empty = rules
+            .text
+            .text
+            .iter()
+            .chain(&rules.web.hosts)
+            .chain(&rules.web.host_parts)
+            .chain(&rules.web.text)
+            .chain(&rules.address.giveaway)
+            .map(|rule| rule.source())
+            .chain(rules.image.text.iter().map(|rule| rule.as_str()))
+            .chain(rules.address.blocklist.iter().map(|rule| rule.as_str()))
+            .any(|rule| rule.trim().is_empty());
+        if empty {
+            return Err(Error::EmptyRule);
+        }
+
+        Ok(feed)
+    }
+
+    /// Merge the rules and image templates of this feed into the given scanner configuration.
+    ///
+    /// Compile the scanner rules again after merging, see `Scanner::compile`.
+    pub fn merge_into(&self, scanner: &mut Scanner) {
+        self.rules.merge_into(scanner);
+        if let Some(dir) = &self.template_dir {
+            scanner.image.template_dirs.push(dir.clone());
+        }
+    }
+}
+
+/// Fetch the feed from the given source, and cache it in the given directory.
+///
+/// The source is a local file path or an HTTP(S) URL. All image templates of the feed are fetched
+/// as well. The cached copy is only replaced once everything is fetched successfully, so it
+/// always holds the last good copy of the feed.
+///
+/// Templates are kept in a directory for each version of the feed, as the current configuration
+/// may still use those of the previous version. Remove those with `prune_cached` once the new
+/// feed is used.
+pub async fn fetch(http: &Clients, source: &str, cache: &Path) -> Result<Feed, Error> {
+    let location = Location::parse(source);
+    let data = location.read(http).await?;
+    let data = String::from_utf8(data).map_err(|_| Error::Encoding)?;
+    let mut feed = Feed::parse(&data)?;
+
+    // Fetch all templates into a new directory, make sure each is a loadable image
+    let key = cache_key(source);
+    let staging = cache.join(format!("{}.new", key));
+    if staging.exists() {
+        fs::remove_dir_all(&staging).map_err(Error::Io)?;
+    }
+    fs::create_dir_all(&staging).map_err(Error::Io)?;
+    let mut hasher = Sha256::new();
+    hasher.input(data.as_bytes());
+    for (i, template) in feed.templates.iter().enumerate() {
+        let template_location = location.join(template)?;
+        let data = template_location.read(http).await?;
+        if let Err(err) = image::load_from_memory(&data) {
+            return Err(Error::Template(template.clone(), err));
+        }
+        let name = format!("{}-{}", i, template_location.file_name());
+        hasher.input(name.as_bytes());
+        hasher.input(&data);
+        fs::write(staging.join(name), data).map_err(Error::Io)?;
+    }
+
+    // Replace the cached copy, keep the templates of an unchanged feed where they are
+    let version = hex(&hasher.result()[..8]);
+    let dir = cache.join(format!("{}-{}", key, version));
+    if dir.exists() {
+        fs::remove_dir_all(&staging).map_err(Error::Io)?;
+    } else {
+        fs::rename(&staging, &dir).map_err(Error::Io)?;
+    }
+    fs::write(cache.join(format!("{}.toml", key)), data).map_err(Error::Io)?;
+    fs::write(cache.join(format!("{}.version", key)), version).map_err(Error::Io)?;
+
+    feed.template_dir = Some(dir);
+    Ok(feed)
+}
+
+/// Load the last good copy of the feed from the given source, from the given cache directory.
+pub fn load_cached(source: &str, cache: &Path) -> Result<Feed, Error> {
+    let key = cache_key(source);
+    let data = fs::read_to_string(cache.join(format!("{}.toml", key))).map_err(Error::Io)?;
+    let version = fs::read_to_string(cache.join(format!("{}.version", key))).map_err(Error::Io)?;
+    let mut feed = Feed::parse(&data)?;
+    feed.template_dir = Some(cache.join(format!("{}-{}", key, version.trim())));
+    Ok(feed)
+}
+
+/// Remove the cached templates of the feed from the given source, except for those in `keep`.
+///
+/// Call this once the configuration uses the templates in `keep`.
+pub fn prune_cached(source: &str, cache: &Path, keep: &Path) -> Result<(), Error> {
+    let prefix = format!("{}-", cache_key(source));
+    for entry in fs::read_dir(cache).map_err(Error::Io)? {
+        let path = entry.map_err(Error::Io)?.path();
+        let name = path.file_name().and_then(|name| name.to_str()).unwrap_or("");
+        if name.starts_with(&prefix) && path.is_dir() && path != keep {
+            fs::remove_dir_all(&path).map_err(Error::Io)?;
+        }
+    }
+    Ok(())
+}
+
+/// Get the name to cache the feed from the given source as.
+///
+/// This is a hash of the source, so different sources never share a name.
+fn cache_key(source: &str) -> String {
+    hex(&Sha256::digest(source.as_bytes())[..16])
+}
+
+/// Format the given bytes as lowercase hexadecimal.
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Location of a feed or image template.
+enum Location {
+    /// An HTTP(S) URL.
+    Url(Url),
+
+    /// A local file.
+    File(PathBuf),
+}
+
+impl Location {
+    /// Parse the given location, as URL if it has an HTTP(S) scheme, or as file path otherwise.
+    fn parse(location: &str) -> Self {
+        match Url::parse(location) {
+            Ok(ref url) if url.scheme() == "http" || url.scheme() == "https" => {
+                Location::Url(url.clone())
+            }
+            _ => Location::File(location.into()),
+        }
+    }
+
+    /// Resolve the given location relative to this location.
+    fn join(&self, location: &str) -> Result<Self, Error> {
+        match (Location::parse(location), self) {
+            (url @ Location::Url(_), _) => Ok(url),
+            (Location::File(_), Location::Url(base)) => {
+                base.join(location).map(Location::Url).map_err(Error::Url)
+            }
+            (Location::File(path), Location::File(base)) => Ok(Location::File(
+                base.parent().unwrap_or_else(|| Path::new(".")).join(path),
+            )),
+        }
+    }
+
+    /// Get the file name of this location.
+    fn file_name(&self) -> String {
+        let name = match self {
+            Location::Url(url) => url.path_segments().and_then(|s| s.last()),
+            Location::File(path) => path.file_name().and_then(|name| name.to_str()),
+        };
+        name.unwrap_or("template").to_owned()
+    }
+
+    /// Read the contents at this location.
+    async fn read(&self, http: &Clients) -> Result<Vec<u8>, Error> {
+        match self {
+            Location::File(path) => fs::read(path).map_err(Error::Io),
+            Location::Url(url) => {
+                let response = http
+                    .download()
+                    .get(url.as_str())
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    .map_err(Error::Request)?;
+                let body = response.bytes().await.map_err(Error::Request)?;
+                Ok(body.to_vec())
+            }
+        }
+    }
+}
+
+/// A rule feed error.
+#[derive(Debug)]
+pub enum Error {
+    /// Failed to read or write a local file.
+    Io(io::Error),
+
+    /// Failed to request the feed or an image template.
+    Request(reqwest::Error),
+
+    /// The feed is not valid UTF-8.
+    Encoding,
+
+    /// The feed is not valid TOML.
+    Toml(toml::de::Error),
+
+    /// The feed has an unsupported format version.
+    Version(u32),
+
+    /// The feed has an empty rule.
+    EmptyRule,
+
+    /// A template URL is invalid.
+    Url(url::ParseError),
+
+    /// The given image template is not a loadable image.
+    Template(String, image::ImageError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Http;
+
+    #[test]
+    fn test_parse() {
+        assert!(Feed::parse("version = 1\n[web]\nhosts = [\"mxevent.site\"]").is_ok());
+        assert!(match Feed::parse("version = 2") {
+            Err(Error::Version(2)) => true,
+            _ => false,
+        });
+        assert!(match Feed::parse("version = 1\n[text]\ntext = [\" \"]") {
+            Err(Error::EmptyRule) => true,
+            _ => false,
+        });
+    }
+
+    #[tokio::test]
+    async fn test_fetch_keeps_last_good_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("cache");
+        let source = dir.path().join("feed.toml");
+        let source = source.to_str().unwrap();
+        fs::copy("./res/illegal/binance.jpg", dir.path().join("binance.jpg")).unwrap();
+        fs::write(
+            source,
+            "version = 1\ntemplates = [\"binance.jpg\"]\n[web]\nhosts = [\"mxevent.site\"]",
+        )
+        .unwrap();
+
+        let http = Clients::new(&Http::default());
+        let feed = fetch(&http, source, &cache).await.unwrap();
+        assert_eq!(feed.rules.web.hosts, ["mxevent.site"]);
+        let template_dir = feed.template_dir.unwrap();
+        assert_eq!(fs::read_dir(&template_dir).unwrap().count(), 1);
+
+        // A broken feed is not cached
+        fs::write(source, "version = 2").unwrap();
+        assert!(fetch(&http, source, &cache).await.is_err());
+        let cached = load_cached(source, &cache).unwrap();
+        assert_eq!(cached.rules.web.hosts, ["mxevent.site"]);
+        assert_eq!(cached.template_dir.unwrap(), template_dir);
+
+        // A new version gets its own templates, the old ones are kept until pruned
+        fs::write(source, "version = 1\ntemplates = [\"binance.jpg\"]").unwrap();
+        let feed = fetch(&http, source, &cache).await.unwrap();
+        let new_template_dir = feed.template_dir.unwrap();
+        assert_ne!(new_template_dir, template_dir);
+        assert!(template_dir.exists());
+        prune_cached(source, &cache, &new_template_dir).unwrap();
+        assert!(!template_dir.exists());
+        assert!(new_template_dir.exists());
+    }
+
+    #[test]
+    fn test_cache_key() {
+        assert_eq!(cache_key("a/b"), cache_key("a/b"));
+        assert_ne!(cache_key("a/b"), cache_key("a_b"));
+    }
+}
@@ -0,0 +1,62 @@
+use std::path::Path;
+use std::time::Duration;
+
+use ban_binance_bot::feed::{self, Feed};
+use tokio::timer::delay_for;
+
+use crate::state::State;
+
+/// Keep the rule feeds up to date.
+///
+/// The cached copies of all feeds are used first, so their rules are available right away. Then
+/// all feeds are fetched at the configured interval. A feed that fails to fetch keeps its last
+/// good copy. This never completes.
+pub async fn watch(state: State) {
+    // Start with the cached copies
+    let config = state.config();
+    let cache = Path::new(&config.feeds.cache);
+    for source in &config.feeds.sources {
+        match feed::load_cached(source, cache) {
+            Ok(feed) => apply(&state, source, cache, feed),
+            Err(err) => debug!("No cached copy of rule feed '{}': {:?}", source, err),
+        }
+    }
+
+    loop {
+        // Use the current configuration, feeds may have been changed by a reload
+        let config = state.config();
+        let cache = Path::new(&config.feeds.cache);
+        for source in &config.feeds.sources {
+            match feed::fetch(&state.context().http, source, cache).await {
+                Ok(feed) => apply(&state, source, cache, feed),
+                Err(err) if state.has_feed(source) => warn!(
+                    "Failed to fetch rule feed '{}', keeping last good copy: {:?}",
+                    source, err
+                ),
+                Err(err) => error!("Failed to fetch rule feed '{}': {:?}", source, err),
+            }
+        }
+
+        delay_for(Duration::from_secs(config.feeds.interval)).await;
+    }
+}
+
+/// Merge the given rule feed into the configuration.
+///
+/// Cached templates of previous versions of the feed are removed once it's used.
+fn apply(state: &State, source: &str, cache: &Path, feed: Feed) {
+    let template_dir = feed.template_dir.clone();
+    match state.set_feed(source, feed) {
+        Ok(()) => info!("Updated rule feed '{}'", source),
+        Err(err) => {
+            error!("Rejected rule feed '{}', keeping last good copy: {}", source, err);
+            return;
+        }
+    }
+
+    if let Some(dir) = template_dir {
+        if let Err(err) = feed::prune_cached(source, cache, &dir) {
+            warn!("Failed to remove old templates of rule feed '{}': {:?}", source, err);
+        }
+    }
+}
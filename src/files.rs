@@ -0,0 +1,194 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use ban_binance_bot::{
+    config::{Action, Config},
+    scanner::{self, file::FileType, score::select_verdicts, Verdict},
+    util,
+};
+use futures::prelude::*;
+use telegram_bot::types::{
+    Error as RawError, File, GetFile, HttpRequest as TelegramRequest, JsonIdResponse,
+    JsonRequestType, Request, RequestType, RequestUrl,
+};
+use url::Url;
+
+use crate::{fingerprints::Fingerprint, state::State};
+
+/// Check whether any of the given Telegram files is illegal.
+///
+/// A list of `GetFile` requests is given, as the actual files should still be downloaded.
+/// Fingerprints of illegal files are only remembered if `record` is set.
+pub async fn has_illegal_files(
+    config: &Config,
+    files: Vec<GetFile>,
+    record: bool,
+    state: State,
+) -> Option<Verdict> {
+    // Build a list of file checks, check them concurrently
+    select_verdicts(
+        &config.scanner.scoring,
+        files
+        .into_iter()
+        .map(|file| is_illegal_file(config, file, record, state.clone()))
+    ).await
+}
+
+/// Check whether the given Telegram file is illegal.
+///
+/// A `GetFile` request is given, as the actual file should still be downloaded.
+/// The file is only downloaded if it can be audited, and if it isn't known as illegal content.
+/// Fingerprints of an illegal file are only remembered if `record` is set.
+pub async fn is_illegal_file(
+    config: &Config,
+    file: GetFile,
+    record: bool,
+    state: State,
+) -> Option<Verdict> {
+    // Request download URL for Telegram file
+    let (file, url) = match request_telegram_file_url(file, state.clone()).await {
+        Ok(data) => data,
+        Err(_) => {
+            warn!("Failed to get Telegram API file URL, could not audit, assuming safe");
+            return None;
+        },
+    };
+
+    // Files posted before are known by their unique ID
+    let mut fingerprints = vec![Fingerprint::File(file.unique_id().into())];
+    if let Some(verdict) = state.fingerprints().find(&config.fingerprints, &fingerprints[0]) {
+        debug!("File matches fingerprint of illegal content");
+        return Some(verdict);
+    }
+
+    // Determine the file type by its name
+    let name = url
+        .path_segments()
+        .and_then(|s| s.last())
+        .unwrap_or("");
+    let file_type = FileType::from_name(name);
+    if !file_type.is_scannable() {
+        debug!("No scanners to audit file type, assuming safe: {}", url);
+        return None;
+    }
+
+    // Skip files that are too large
+    match file.file.file_size {
+        Some(size) if size > file_type.max_size(&config.scanner) => {
+            info!("File to large to audit, assuming safe");
+            return None;
+        },
+        _ => {},
+    };
+
+    // Download the file to a temporary file to test on
+    let path = match util::download::download_temp(&state.context().http, &url).await {
+        Ok(response) => response.1,
+        Err(err) => {
+            warn!("Failed to download file, could not audit, assuming safe: {:?}", err);
+            return None;
+        }
+    };
+
+    // Images may be posted again as resized or recompressed copy, match their perceptual hash
+    if file_type == FileType::Image {
+        if let Ok(hash) = util::image::dhash(Arc::new(path.to_path_buf())).await {
+            let fingerprint = Fingerprint::Image(hash);
+            if let Some(verdict) = state.fingerprints().find(&config.fingerprints, &fingerprint) {
+                debug!("Image matches fingerprint of illegal content");
+                return Some(verdict);
+            }
+            fingerprints.push(fingerprint);
+        }
+    }
+
+    // Test whether the file is illegal, remember its fingerprints if so
+    let verdict = scanner::file::is_illegal_file(&config.scanner, &state.context(), &path).await;
+    if let (true, Some(verdict)) = (record, &verdict) {
+        if config.scanner.scoring.action(Some(verdict)) >= Action::Delete {
+            for fingerprint in fingerprints {
+                state.fingerprints().insert(&config.fingerprints, fingerprint, verdict);
+            }
+        }
+    }
+    verdict
+}
+
+/// Get `File` for Telegram API `GetFile`, along with its unique ID.
+async fn request_telegram_file(file: GetFile, state: State) -> Result<UniqueFile, ()> {
+    state
+        .telegram_client()
+        .send_timeout(GetUniqueFile { file }, Duration::from_secs(30))
+        .map_err(|err| {
+            error!("Failed to send file data request to Telegram API: {:?}", err);
+            ()
+        })
+        .await
+        .map_err(|err| {
+            error!("Failed to request file data from Telegram API: {:?}", err);
+            ()
+        })
+        .and_then(|file| file.ok_or_else(|| {
+            error!("Expected file data from Telegram API, but did not receive anything");
+            ()
+        }))
+}
+
+/// Get download URL for Telegram API `GetFile`.
+async fn request_telegram_file_url(file: GetFile, state: State) -> Result<(UniqueFile, Url), ()> {
+    // Request Telegram file
+    let file = request_telegram_file(file, state.clone()).await?;
+
+    // Build URL
+    file.file.get_url(state.token())
+        .ok_or_else(|| {
+            error!("No download URL for Telegram API file provided");
+            ()
+        })
+        .and_then(|url| match Url::parse(&url) {
+            Ok(url) => Ok((file, url)),
+            Err(err) => {
+                error!("Failed to parse file URL from Telegram API: {}", err);
+                Err(())
+            }
+        })
+}
+
+/// Telegram API request to get a file, like `GetFile`.
+///
+/// The `File` type of `telegram-bot` doesn't include the unique file ID yet, so this parses it
+/// from the response along with the file.
+#[derive(Serialize, Debug)]
+struct GetUniqueFile {
+    #[serde(flatten)]
+    file: GetFile,
+}
+
+impl Request for GetUniqueFile {
+    type Type = JsonRequestType<Self>;
+    type Response = JsonIdResponse<UniqueFile>;
+
+    fn serialize(&self) -> Result<TelegramRequest, RawError> {
+        Self::Type::serialize(RequestUrl::method("getFile"), self)
+    }
+}
+
+/// A Telegram file, along with its unique ID.
+#[derive(Deserialize, Debug)]
+struct UniqueFile {
+    #[serde(flatten)]
+    file: File,
+
+    /// Unique file ID, which is the same for every upload of the file and for every bot.
+    file_unique_id: Option<String>,
+}
+
+impl UniqueFile {
+    /// Get the unique file ID.
+    ///
+    /// Falls back to the regular file ID if the Telegram API didn't send one, which may differ
+    /// between uploads of the same file.
+    fn unique_id(&self) -> &str {
+        self.file_unique_id.as_ref().unwrap_or(&self.file.file_id)
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use ban_binance_bot::{config::Fingerprints, scanner::Verdict, util::image::hash_distance};
+
+/// Interval to forget expired fingerprints at.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A fingerprint of content, such as message text or a file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Fingerprint {
+    /// Fingerprint of message text, see `util::text::fingerprint`.
+    Text(u64),
+
+    /// Telegram unique file ID, which is the same for every upload of a file, and for every bot.
+    File(String),
+
+    /// Perceptual hash of an image, see `util::image::dhash`.
+    Image(u64),
+}
+
+/// Store of fingerprints of illegal content, shared between all chats.
+///
+/// Content posted again in any chat is recognized without auditing it again, such as without
+/// downloading files or fetching URLs. Fingerprints are kept in memory, and expire.
+#[derive(Clone, Default)]
+pub struct FingerprintStore {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// Illegal content by fingerprint, with the verdict and the time it was judged.
+    illegal: HashMap<Fingerprint, (Verdict, Instant)>,
+
+    /// Chats each text fingerprint was seen in, with the time it was last seen.
+    seen: HashMap<u64, HashMap<i64, Instant>>,
+
+    /// Time expired fingerprints were last forgotten.
+    swept: Option<Instant>,
+}
+
+impl Inner {
+    /// Forget expired fingerprints, at most once every `SWEEP_INTERVAL`.
+    ///
+    /// Expired fingerprints are ignored when looked up, this only frees their memory.
+    fn sweep(&mut self, config: &Fingerprints) {
+        if self.swept.map_or(false, |time| time.elapsed() < SWEEP_INTERVAL) {
+            return;
+        }
+        self.swept = Some(Instant::now());
+
+        let ttl = Duration::from_secs(config.ttl);
+        let window = Duration::from_secs(config.flood_window);
+        self.illegal.retain(|_, (_, time)| time.elapsed() < ttl);
+        self.seen.retain(|_, chats| {
+            chats.retain(|_, time| time.elapsed() < window);
+            !chats.is_empty()
+        });
+    }
+}
+
+impl FingerprintStore {
+    /// Find the verdict of illegal content matching the given fingerprint.
+    ///
+    /// Images match images with a perceptual hash within the configured distance.
+    pub fn find(&self, config: &Fingerprints, fingerprint: &Fingerprint) -> Option<Verdict> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.sweep(config);
+        let ttl = Duration::from_secs(config.ttl);
+
+        if let Some((verdict, time)) = inner.illegal.get(fingerprint) {
+            if time.elapsed() < ttl {
+                return Some(verdict.clone());
+            }
+        }
+        match fingerprint {
+            Fingerprint::Image(hash) => inner
+                .illegal
+                .iter()
+                .find(|(other, (_, time))| match other {
+                    Fingerprint::Image(other) => {
+                        time.elapsed() < ttl
+                            && hash_distance(*hash, *other) <= config.image_distance
+                    }
+                    _ => false,
+                })
+                .map(|(_, (verdict, _))| verdict.clone()),
+            _ => None,
+        }
+    }
+
+    /// Remember the given fingerprint as illegal content, with its verdict.
+    pub fn insert(&self, config: &Fingerprints, fingerprint: Fingerprint, verdict: &Verdict) {
+        if config.ttl == 0 {
+            return;
+        }
+        self.inner
+            .lock()
+            .unwrap()
+            .illegal
+            .insert(fingerprint, (verdict.clone(), Instant::now()));
+    }
+
+    /// Record that text with the given fingerprint is posted in the given chat.
+    ///
+    /// Returns the number of different chats it was posted in within the flood window, including
+    /// the given chat.
+    pub fn see_text(&self, config: &Fingerprints, fingerprint: u64, chat: i64) -> usize {
+        let mut inner = self.inner.lock().unwrap();
+        inner.sweep(config);
+        let window = Duration::from_secs(config.flood_window);
+
+        // Forget chats this text was seen in outside the window
+        let chats = inner.seen.entry(fingerprint).or_insert_with(HashMap::new);
+        chats.retain(|_, time| time.elapsed() < window);
+        chats.insert(chat, Instant::now());
+        chats.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ban_binance_bot::scanner::verdict::{Kind, Rule};
+
+    #[test]
+    fn test_find() {
+        let config = Fingerprints::default();
+        let store = FingerprintStore::default();
+        let template = Rule::Template {
+            template: "binance.jpg".into(),
+            score: 0.2,
+        };
+        let verdict = Verdict::new(Kind::Image, template);
+        store.insert(&config, Fingerprint::File("file".into()), &verdict);
+        store.insert(&config, Fingerprint::Image(0b1111_0000), &verdict);
+
+        assert!(store.find(&config, &Fingerprint::File("file".into())).is_some());
+        assert!(store.find(&config, &Fingerprint::File("other".into())).is_none());
+        assert!(store.find(&config, &Fingerprint::Image(0b1111_0011)).is_some());
+        assert!(store.find(&config, &Fingerprint::Image(0b0000_1111)).is_none());
+        assert!(store.find(&config, &Fingerprint::Text(0b1111_0000)).is_none());
+    }
+
+    #[test]
+    fn test_see_text() {
+        let config = Fingerprints::default();
+        let store = FingerprintStore::default();
+        assert_eq!(store.see_text(&config, 1, -1), 1);
+        assert_eq!(store.see_text(&config, 1, -1), 1);
+        assert_eq!(store.see_text(&config, 1, -2), 2);
+        assert_eq!(store.see_text(&config, 2, -3), 1);
+
+        // Chats outside the flood window are forgotten
+        let config = Fingerprints {
+            flood_window: 0,
+            ..Fingerprints::default()
+        };
+        assert_eq!(store.see_text(&config, 1, -4), 1);
+    }
+}
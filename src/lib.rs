@@ -0,0 +1,18 @@
+//! Scanners to detect Binance promotion spam in text, URLs, images and videos.
+//!
+//! This library holds the detection logic used by the Telegram bot, and can be used on its own
+//! with plain inputs such as text, a list of URLs or a file path.
+
+#[macro_use]
+extern crate lazy_static;
+#[macro_use]
+extern crate log;
+#[macro_use]
+extern crate serde;
+
+pub mod classifier;
+pub mod config;
+pub mod corpus;
+pub mod feed;
+pub mod scanner;
+pub mod util;
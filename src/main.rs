@@ -1,5 +1,5 @@
 #[macro_use]
-extern crate lazy_static;
+extern crate clap;
 #[macro_use]
 extern crate log;
 #[macro_use]
@@ -7,17 +7,28 @@ extern crate serde;
 
 #[cfg(feature = "sentry")]
 use std::env;
+use std::process;
 
+use ban_binance_bot::config::Config;
 use dotenv::dotenv;
 
 use state::State;
 
+mod archive;
 mod bot;
-mod config;
-mod scanner;
+mod chats;
+mod cmd;
+mod commands;
+mod feeds;
+mod files;
+mod fingerprints;
+mod reload;
+mod retry;
+mod scheduler;
+mod signal;
 mod state;
 mod traits;
-mod util;
+mod webhook;
 
 use bot::UpdateError;
 
@@ -29,6 +40,50 @@ async fn main() -> Result<(), UpdateError> {
     // Enable logging
     env_logger::init();
 
+    // Parse command line arguments
+    let matches = cmd::app().get_matches();
+
+    // Run config check command if invoked
+    let config_path = cmd::config_path(&matches);
+    if let Some(matches) = matches.subcommand_matches("check-config") {
+        if !cmd::check_config::invoke(matches, config_path) {
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // Run classifier training command if invoked
+    if let Some(matches) = matches.subcommand_matches("train") {
+        if let Err(err) = cmd::train::invoke(matches) {
+            eprintln!("Failed to train classifier: {:?}", err);
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // Load configuration, apply environment overrides
+    info!("Loading configuration from {}...", config_path);
+    let config = match Config::load(config_path) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to load configuration from {}: {}", config_path, err);
+            process::exit(1);
+        }
+    };
+    if let Err(err) = config.validate() {
+        eprintln!("Invalid configuration in {}: {}, run check-config for details", config_path, err);
+        process::exit(1);
+    }
+
+    // Run offline scan command if invoked
+    if let Some(matches) = matches.subcommand_matches("scan") {
+        if let Err(err) = cmd::scan::invoke(matches, &config).await {
+            eprintln!("Failed to scan: {:?}", err);
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
     // Initialize Sentry
     #[cfg(feature = "sentry")]
     let _guard = init_sentry();
@@ -36,7 +91,7 @@ async fn main() -> Result<(), UpdateError> {
     info!("Starting Telegram bot...");
 
     // Initialize the global state
-    let state = match State::init().await {
+    let state = match State::init(config).await {
         Ok(state) => state,
         Err(err) => {
             error!("Failed to initialize bot state: {:?}", err);
@@ -45,6 +100,10 @@ async fn main() -> Result<(), UpdateError> {
     };
     debug!("Bot has been initialized");
 
+    // Reload configuration on hangup signal or when changed, keep rule feeds up to date
+    tokio::spawn(reload::watch(state.clone(), config_path.to_owned()));
+    tokio::spawn(feeds::watch(state.clone()));
+
     // Build the application, attach signal handling
     let app = bot::build_telegram_handler(state.clone()).await;
     match &app {
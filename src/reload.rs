@@ -0,0 +1,90 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use ban_binance_bot::config::{self, Config};
+use futures::{prelude::*, stream};
+use tokio::timer::Interval;
+
+use crate::{signal, state::State};
+
+/// Interval to check the configuration file and image template directory for changes.
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Watch for configuration changes, and reload the configuration when changed.
+///
+/// The configuration is reloaded when the process receives `SIGHUP`, or when the configuration
+/// file, an included rule file, the classifier model or the image template directory is
+/// modified. This never completes.
+pub async fn watch(state: State, path: String) {
+    let mut last = modified(&path, &state.config());
+
+    // Reload on hangup signal, or poll for changes
+    let mut triggers = stream::select(
+        signal::reload().map(|_| true),
+        Interval::new_interval(WATCH_INTERVAL).map(|_| false),
+    );
+
+    while let Some(hangup) = triggers.next().await {
+        let (state, path) = (state.clone(), path.clone());
+        last = tokio_executor::blocking::run(move || {
+            // Only reload on change, unless signalled
+            let current = modified(&path, &state.config());
+            if hangup {
+                info!("Received hangup signal, reloading configuration...");
+            } else if current != last {
+                info!("Configuration or image templates changed, reloading configuration...");
+            } else {
+                return last;
+            }
+
+            match reload(&state, &path) {
+                Ok(()) => info!("Reloaded configuration"),
+                Err(err) => error!(
+                    "Failed to reload configuration, keeping current configuration: {}",
+                    err
+                ),
+            }
+
+            // The image directory may have changed
+            modified(&path, &state.config())
+        })
+        .await;
+    }
+}
+
+/// Reload the configuration from the given path.
+///
+/// The new configuration is validated before it replaces the current configuration. If loading
+/// or validation fails, the current configuration is kept.
+pub fn reload(state: &State, path: &str) -> Result<(), config::Error> {
+    let config = Config::load(path)?;
+    config.validate()?;
+    state.set_config(config);
+    Ok(())
+}
+
+/// Get the modification times of the configuration file, included rule files, the classifier
+/// model, and of the image template directory and its files.
+///
+/// Comparing these with an earlier result tells whether anything changed.
+fn modified(path: &str, config: &Config) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let mut paths = vec![PathBuf::from(path)];
+    paths.extend(config.included.iter().cloned());
+    paths.extend(config.scanner.classifier.loaded_path.clone());
+    if let Some(dir) = &config.scanner.image.dir {
+        paths.push(dir.into());
+        if let Ok(entries) = fs::read_dir(dir) {
+            paths.extend(entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()));
+        }
+    }
+    paths.sort();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let time = fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+            (path, time)
+        })
+        .collect()
+}
@@ -0,0 +1,148 @@
+use std::cmp;
+use std::time::Duration;
+
+use telegram_bot::{
+    types::{ErrorKind as RawErrorKind, ResponseParameters},
+    Error as TelegramError, ErrorKind,
+};
+
+/// Initial delay before retrying after an error.
+const BACKOFF_INITIAL: Duration = Duration::from_secs(1);
+
+/// Maximum delay before retrying after an error.
+const BACKOFF_MAX: Duration = Duration::from_secs(120);
+
+/// Descriptions the Telegram Bot API responds with for every request when the bot token is
+/// invalid or revoked.
+const FATAL_DESCRIPTIONS: [&str; 2] = ["Unauthorized", "Not Found"];
+
+/// Classification of a Telegram API error, to determine how to recover from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// Fatal error, such as an invalid bot token. Retrying won't help.
+    Fatal,
+
+    /// Telegram asks us to slow down, retry after the given number of seconds.
+    FloodWait(u64),
+
+    /// Telegram API server error, such as a gateway error page instead of an API response.
+    /// Retry with backoff.
+    Server,
+
+    /// Network error, or any other error we don't know. Retry with backoff.
+    Network,
+}
+
+impl ErrorClass {
+    /// Classify the given Telegram API error.
+    ///
+    /// The Telegram API client does not expose the error code of API errors. Only an invalid
+    /// bot token is fatal, recognized by the exact description the Bot API responds with. Any
+    /// error that isn't known is assumed to be temporary.
+    pub fn of(err: &TelegramError) -> Self {
+        let kind = match err.kind() {
+            ErrorKind::Raw(kind) => kind,
+            _ => return ErrorClass::Network,
+        };
+
+        match kind {
+            // Flood wait, respect the requested delay
+            RawErrorKind::TelegramError {
+                parameters:
+                    Some(ResponseParameters {
+                        retry_after: Some(seconds),
+                        ..
+                    }),
+                ..
+            } => ErrorClass::FloodWait(cmp::max(*seconds, 1) as u64),
+            RawErrorKind::TelegramError { description, .. }
+                if FATAL_DESCRIPTIONS.contains(&description.as_str()) =>
+            {
+                ErrorClass::Fatal
+            }
+            RawErrorKind::TelegramError { .. } => ErrorClass::Network,
+
+            // Responses that aren't API responses at all come from failing Telegram servers
+            RawErrorKind::EmptyBody | RawErrorKind::Json(_) => ErrorClass::Server,
+            _ => ErrorClass::Network,
+        }
+    }
+}
+
+/// Exponential backoff for retrying failed requests.
+#[derive(Debug)]
+pub struct Backoff {
+    /// The delay to use for the next retry.
+    delay: Duration,
+}
+
+impl Backoff {
+    /// Get the delay to wait before the next retry, and increase the delay after that.
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.delay;
+        self.delay = cmp::min(self.delay * 2, BACKOFF_MAX);
+        delay
+    }
+
+    /// Reset the delay after a successful request.
+    pub fn reset(&mut self) {
+        self.delay = BACKOFF_INITIAL;
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            delay: BACKOFF_INITIAL,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a Telegram API error response with the given description and retry delay.
+    fn api_error(description: &str, retry_after: Option<i64>) -> TelegramError {
+        let parameters = ResponseParameters {
+            migrate_to_chat_id: None,
+            retry_after,
+        };
+        ErrorKind::Raw(RawErrorKind::TelegramError {
+            description: description.into(),
+            parameters: Some(parameters),
+        })
+        .into()
+    }
+
+    #[test]
+    fn test_classify() {
+        let class = |err: TelegramError| ErrorClass::of(&err);
+        assert_eq!(class(api_error("Unauthorized", None)), ErrorClass::Fatal);
+        assert_eq!(class(api_error("Not Found", None)), ErrorClass::Fatal);
+        assert_eq!(
+            class(api_error("Too Many Requests: retry after 35", Some(35))),
+            ErrorClass::FloodWait(35),
+        );
+        assert_eq!(
+            class(api_error("Bad Request: message to delete not found", None)),
+            ErrorClass::Network,
+        );
+        assert_eq!(class(ErrorKind::Raw(RawErrorKind::EmptyBody).into()), ErrorClass::Server);
+        assert_eq!(class(ErrorKind::Msg("unknown".into()).into()), ErrorClass::Network);
+    }
+
+    #[test]
+    fn test_backoff() {
+        let mut backoff = Backoff::default();
+        assert_eq!(backoff.next_delay(), Duration::from_secs(1));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(2));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(4));
+        for _ in 0..10 {
+            backoff.next_delay();
+        }
+        assert_eq!(backoff.next_delay(), BACKOFF_MAX);
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,298 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tempfile::NamedTempFile;
+use url::Url;
+
+use crate::config;
+use super::verdict::Verdict;
+
+/// Cache of URL audits, so URLs posted again are audited without any network requests.
+///
+/// This keeps the URL that redirects lead to, and the verdict of its webpage content. Entries
+/// expire, and the least recently used entry is dropped when the cache is full. The cache is kept
+/// in memory, and is written to a JSON file on `save` if a path is set. This is cheap to clone,
+/// clones share the same entries.
+#[derive(Clone)]
+pub struct UrlCache {
+    /// File the cache is stored in.
+    path: Option<PathBuf>,
+
+    /// Maximum number of entries, `0` to disable the cache.
+    size: usize,
+
+    /// Time in seconds entries are valid for.
+    ttl: u64,
+
+    /// Cached entries, by normalized URL.
+    entries: Arc<Mutex<Entries>>,
+}
+
+/// Cached entries, ordered by their use.
+#[derive(Default)]
+struct Entries {
+    /// Entries by normalized URL.
+    entries: HashMap<String, Entry>,
+
+    /// Normalized URLs by the value of the use counter when their entry was last used.
+    ///
+    /// The first URL is the least recently used one.
+    order: BTreeMap<u64, String>,
+
+    /// Counter, incremented on each use of an entry.
+    counter: u64,
+}
+
+impl Entries {
+    /// Build entries, with the given entries ordered by the time they were audited at.
+    fn new(entries: HashMap<String, Entry>) -> Self {
+        let mut keys: Vec<(u64, String)> =
+            entries.iter().map(|(key, entry)| (entry.time, key.clone())).collect();
+        keys.sort();
+
+        let mut ordered = Self {
+            entries,
+            ..Self::default()
+        };
+        for (_, key) in keys {
+            ordered.touch(&key);
+        }
+        ordered
+    }
+
+    /// Mark the entry with the given key as most recently used.
+    fn touch(&mut self, key: &str) -> Option<&Entry> {
+        self.counter += 1;
+        let counter = self.counter;
+        let entry = self.entries.get_mut(key)?;
+        self.order.remove(&entry.used);
+        entry.used = counter;
+        self.order.insert(counter, key.into());
+        Some(&*entry)
+    }
+
+    /// Remove the entry with the given key.
+    fn remove(&mut self, key: &str) -> Option<Entry> {
+        let entry = self.entries.remove(key)?;
+        self.order.remove(&entry.used);
+        Some(entry)
+    }
+
+    /// Remove the least recently used entry.
+    fn remove_oldest(&mut self) {
+        let key = match self.order.values().next() {
+            Some(key) => key.clone(),
+            None => return,
+        };
+        self.remove(&key);
+    }
+}
+
+/// A cached URL audit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    /// The URL redirects lead to.
+    pub target: Url,
+
+    /// The verdict of the webpage content at the target, `None` if it's legal.
+    pub verdict: Option<Verdict>,
+
+    /// Revision of the web rules the verdict was made with, see `config::Web::revision`.
+    ///
+    /// The verdict must not be trusted if the rules have another revision.
+    #[serde(default)]
+    pub revision: u64,
+
+    /// Time the URL was audited at, in seconds since the Unix epoch.
+    time: u64,
+
+    /// Value of the use counter when this entry was last used.
+    #[serde(skip)]
+    used: u64,
+}
+
+impl UrlCache {
+    /// Create the cache with the given configuration.
+    ///
+    /// If a path is configured, entries are loaded from it. The cache starts empty if the file
+    /// doesn't exist yet, or if it can't be loaded.
+    pub fn new(config: &config::UrlCache) -> Self {
+        let path = config.path.as_ref().map(PathBuf::from);
+        let entries = match &path {
+            Some(path) => load(path).unwrap_or_else(|err| {
+                warn!("Failed to load URL cache, starting empty: {:?}", err);
+                HashMap::new()
+            }),
+            None => HashMap::new(),
+        };
+
+        Self {
+            path,
+            size: config.size,
+            ttl: config.ttl,
+            entries: Arc::new(Mutex::new(Entries::new(entries))),
+        }
+    }
+
+    /// Get the cached audit of the given URL.
+    ///
+    /// Returns `None` if the URL isn't cached, or if its entry has expired.
+    pub fn get(&self, url: &Url) -> Option<Entry> {
+        let mut entries = self.entries.lock().unwrap();
+        let key = normalize(url);
+        match entries.entries.get(&key) {
+            Some(entry) if now().saturating_sub(entry.time) < self.ttl => {}
+            Some(_) => {
+                entries.remove(&key);
+                return None;
+            }
+            None => return None,
+        }
+
+        entries.touch(&key).cloned()
+    }
+
+    /// Cache the audit of the given URL, made with the given revision of the web rules.
+    ///
+    /// Drops the least recently used entry if the cache is full.
+    pub fn insert(&self, url: &Url, target: Url, verdict: Option<Verdict>, revision: u64) {
+        if self.size == 0 || self.ttl == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        let key = normalize(url);
+        entries.remove(&key);
+        let entry = Entry {
+            target,
+            verdict,
+            revision,
+            time: now(),
+            used: 0,
+        };
+        entries.entries.insert(key.clone(), entry);
+        entries.touch(&key);
+
+        // Drop the least recently used entries
+        while entries.entries.len() > self.size {
+            entries.remove_oldest();
+        }
+    }
+
+    /// Remove the cached audit of the given URL, so it's audited again.
+    ///
+    /// Returns `true` if the URL was cached.
+    pub fn invalidate(&self, url: &Url) -> bool {
+        self.entries.lock().unwrap().remove(&normalize(url)).is_some()
+    }
+
+    /// Write the cache to its file, if a path is set.
+    ///
+    /// A temporary file is written first and then moved into place, so the file is never left
+    /// partially written.
+    pub fn save(&self) -> Result<(), Error> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let data = serde_json::to_vec(&self.entries.lock().unwrap().entries).map_err(Error::Json)?;
+
+        let dir = path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."));
+        let mut file = NamedTempFile::new_in(dir).map_err(Error::Io)?;
+        file.write_all(&data).map_err(Error::Io)?;
+        file.persist(path).map_err(|err| Error::Io(err.error))?;
+        Ok(())
+    }
+}
+
+/// Normalize the given URL, to use as cache key.
+///
+/// The scheme and host are lowercase already, this drops the fragment as it's never sent.
+pub fn normalize(url: &Url) -> String {
+    let mut url = url.clone();
+    url.set_fragment(None);
+    url.into_string()
+}
+
+/// Load cache entries from the given file.
+///
+/// Returns no entries if the file doesn't exist.
+fn load(path: &Path) -> Result<HashMap<String, Entry>, Error> {
+    match fs::read(path) {
+        Ok(data) => serde_json::from_slice(&data).map_err(Error::Json),
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(HashMap::new()),
+        Err(err) => Err(Error::Io(err)),
+    }
+}
+
+/// Get the current time, in seconds since the Unix epoch.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or(0)
+}
+
+/// A URL cache error.
+#[derive(Debug)]
+pub enum Error {
+    /// Failed to read or write the cache file.
+    Io(io::Error),
+
+    /// Failed to parse or serialize the cache file.
+    Json(serde_json::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::verdict::{Kind, Rule};
+
+    #[test]
+    fn test_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = config::UrlCache {
+            size: 2,
+            path: Some(dir.path().join("urls.json").to_str().unwrap().into()),
+            ..config::UrlCache::default()
+        };
+        let cache = UrlCache::new(&config);
+        let url = |url: &str| Url::parse(url).unwrap();
+        let verdict = Verdict::new(Kind::Url, Rule::Webpage { needle: "Binance".into() });
+
+        cache.insert(&url("https://bit.ly/a"), url("https://mxevent.site/"), Some(verdict), 1);
+        cache.insert(&url("https://bit.ly/b"), url("https://example.com/"), None, 1);
+        assert!(cache.get(&url("https://BIT.ly/a#top")).unwrap().verdict.is_some());
+        assert!(cache.get(&url("https://bit.ly/b")).unwrap().verdict.is_none());
+        assert!(cache.get(&url("https://bit.ly/c")).is_none());
+
+        // The least recently used entry is dropped
+        cache.insert(&url("https://bit.ly/c"), url("https://example.org/"), None, 2);
+        assert!(cache.get(&url("https://bit.ly/a")).is_none());
+        assert!(cache.get(&url("https://bit.ly/b")).is_some());
+
+        // Inserting again replaces the entry, and marks it as used
+        cache.insert(&url("https://bit.ly/c"), url("https://example.net/"), None, 2);
+        cache.insert(&url("https://bit.ly/d"), url("https://example.org/"), None, 2);
+        assert!(cache.get(&url("https://bit.ly/b")).is_none());
+        let entry = cache.get(&url("https://bit.ly/c")).unwrap();
+        assert_eq!(entry.target, url("https://example.net/"));
+
+        // Entries survive a restart
+        cache.save().unwrap();
+        let cache = UrlCache::new(&config);
+        let entry = cache.get(&url("https://bit.ly/c")).unwrap();
+        assert_eq!(entry.target, url("https://example.net/"));
+        assert_eq!(entry.revision, 2);
+        assert!(cache.invalidate(&url("https://bit.ly/c")));
+        assert!(cache.get(&url("https://bit.ly/c")).is_none());
+        assert!(!cache.invalidate(&url("https://bit.ly/c")));
+    }
+}
@@ -1,183 +1,157 @@
-use std::sync::Arc;
-use std::time::Duration;
+use std::path::{Path, PathBuf};
 
-use futures::prelude::*;
-use telegram_bot::types::{GetFile, File};
-use url::Url;
+use crate::config::Scanner;
+use super::score::select_verdicts;
+#[cfg(feature = "ffmpeg")]
+use crate::util;
+#[cfg(feature = "ffmpeg")]
+use super::verdict::Kind;
+use super::verdict::Verdict;
+use super::Context;
 
-use crate::{
-    config::Scanner,
-    // TODO: remove this!
-    config::*,
-    state::State,
-    util::{self, future::select_true},
-};
+/// A file type, determining what scanner is used to audit a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    /// An image file.
+    Image,
 
-/// Check whether any of the given files is illegal.
-///
-/// A list of `GetFile` requests is given, as the actual files should still be downloaded.
-pub async fn has_illegal_files(config: &Scanner, files: Vec<GetFile>, state: State) -> bool {
-    // Build a list of file checks, check them concurrently
-    select_true(
-        files
-        .into_iter()
-        .map(|file| is_illegal_file(config, file, state.clone()))
-    ).await
-}
+    /// A video file.
+    Video,
 
-/// Check whether the given file is illegal.
-///
-/// A `GetFile` request is given, as the actual file should still be downloaded.
-pub async fn is_illegal_file(config: &Scanner, file: GetFile, state: State) -> bool {
-    // Request download URL for Telegram file
-    let (file, url) = match request_telegram_file_url(file, state).await {
-        Ok(data) => data,
-        Err(_) => {
-            warn!("Failed to get Telegram API file URL, could not audit, assuming safe");
-            return false;
-        },
-    };
+    /// An animated Telegram sticker.
+    AnimatedSticker,
 
-    // Skip files that are too large
-    match file.file_size {
-        Some(size) if size > MAX_FILE_SIZE => {
-            info!("File to large to audit, assuming safe");
-            return false;
-        },
-        _ => {},
-    };
+    /// An unknown file type, no scanner is available.
+    Unknown,
+}
 
-    // Do tests based on file extension
+impl FileType {
+    /// Determine the file type from the given file name or path, based on its extension.
     // TODO: better extension test
-    let url_path = url
-        .path_segments()
-        .and_then(|s| s.last())
-        .map(|s| s.to_lowercase())
-        .unwrap_or_else(|| "".into());
-    if url_path.ends_with(".jpg")
-        || url_path.ends_with(".jpeg")
-        || url_path.ends_with(".png")
-        || url_path.ends_with(".gif")
-        || url_path.ends_with(".tiff")
-        || url_path.ends_with(".bmp")
-        || url_path.ends_with(".ico")
-        || url_path.ends_with(".pnm")
-        || url_path.ends_with(".pbm")
-        || url_path.ends_with(".pgm")
-        || url_path.ends_with(".ppm")
-        || url_path.ends_with(".pam")
-        || url_path.ends_with(".webp") {
-        if is_illegal_image(config, file, &url).await {
-            return true;
+    pub fn from_name(name: &str) -> Self {
+        let name = name.to_lowercase();
+        if name.ends_with(".jpg")
+            || name.ends_with(".jpeg")
+            || name.ends_with(".png")
+            || name.ends_with(".gif")
+            || name.ends_with(".tiff")
+            || name.ends_with(".bmp")
+            || name.ends_with(".ico")
+            || name.ends_with(".pnm")
+            || name.ends_with(".pbm")
+            || name.ends_with(".pgm")
+            || name.ends_with(".ppm")
+            || name.ends_with(".pam")
+            || name.ends_with(".webp") {
+            FileType::Image
+        } else if name.ends_with(".mts")
+            || name.ends_with(".avi")
+            || name.ends_with(".flv")
+            || name.ends_with(".mpeg")
+            || name.ends_with(".mp4")
+            || name.ends_with(".wmv")
+            || name.ends_with(".mov")
+            || name.ends_with(".webm") {
+            FileType::Video
+        } else if name.ends_with(".tgs") {
+            FileType::AnimatedSticker
+        } else {
+            FileType::Unknown
         }
-    } else if url_path.ends_with(".mts")
-        || url_path.ends_with(".avi")
-        || url_path.ends_with(".flv")
-        || url_path.ends_with(".mpeg")
-        || url_path.ends_with(".mp4")
-        || url_path.ends_with(".wmv")
-        || url_path.ends_with(".mov")
-        || url_path.ends_with(".webm") {
-        #[cfg(feature = "ffmpeg")]
-        {
-            if is_illegal_video(config, file, &url).await {
-                return true;
-            }
+    }
+
+    /// Get the maximum size in bytes of files of this type to audit.
+    pub fn max_size(self, config: &Scanner) -> i64 {
+        match self {
+            FileType::Image => config.image.max_file_size,
+            _ => config.file.max_size,
         }
-    } else if url_path.ends_with(".tgs") {
-        debug!("No scanner for animated Telegram stickers, assuming safe: {}", url);
-    } else {
-        warn!("No scanners to audit file type, assuming safe: {}", url);
     }
 
-    false
+    /// Check whether a scanner is available to audit this file type.
+    pub fn is_scannable(self) -> bool {
+        match self {
+            FileType::Image => true,
+            FileType::Video => cfg!(feature = "ffmpeg"),
+            FileType::AnimatedSticker | FileType::Unknown => false,
+        }
+    }
 }
 
-/// Get `File` for Telegram API `GetFile`.
-async fn request_telegram_file(file: GetFile, state: State) -> Result<File, ()> {
-    state
-        .telegram_client()
-        .send_timeout(file, Duration::from_secs(30))
-        .map_err(|err| {
-            error!("Failed to send file data request to Telegram API: {:?}", err);
-            ()
-        })
-        .await
-        .map_err(|err| {
-            error!("Failed to request file data from Telegram API: {:?}", err);
-            ()
-        })
-        .and_then(|file| file.ok_or_else(|| {
-            error!("Expected file data from Telegram API, but did not receive anything");
-            ()
-        }))
+/// Check whether any of the files at the given paths is illegal.
+pub async fn has_illegal_files(
+    config: &Scanner,
+    context: &Context,
+    paths: &[PathBuf],
+) -> Option<Verdict> {
+    // Build a list of file checks, check them concurrently
+    select_verdicts(
+        &config.scoring,
+        paths
+        .iter()
+        .map(|path| is_illegal_file(config, context, path))
+    ).await
 }
 
-/// Get download URL for Telegram API `GetFile`.
-async fn request_telegram_file_url(file: GetFile, state: State) -> Result<(File, Url), ()> {
-    // Request Telegram file
-    let file = request_telegram_file(file, state.clone()).await?;
-
-    // Build URL
-    file.get_url(state.token())
-        .ok_or_else(|| {
-            error!("No download URL for Telegram API file provided");
-            ()
-        })
-        .and_then(|url| match Url::parse(&url) {
-            Ok(url) => Ok((file, url)),
-            Err(err) => {
-                error!("Failed to parse file URL from Telegram API: {}", err);
-                Err(())
-            }
-        })
-}
+/// Check whether the file at the given path is illegal.
+///
+/// The scanner to use is determined by the file extension.
+pub async fn is_illegal_file(config: &Scanner, context: &Context, path: &Path) -> Option<Verdict> {
+    let file_type = FileType::from_name(path.to_str().unwrap_or(""));
 
-/// Check whether the given Telegram image is an illegal file.
-async fn is_illegal_image(config: &Scanner, file: File, url: &Url) -> bool {
-    // Skip images that are too large
-    match file.file_size {
-        Some(size) if size > IMAGE_MAX_FILE_SIZE => {
-            info!("Image file too large to audit, assuming safe");
-            return false;
+    // Skip files that are too large
+    match path.metadata() {
+        Ok(metadata) if metadata.len() > file_type.max_size(config) as u64 => {
+            info!("File to large to audit, assuming safe");
+            return None;
         },
-        _ => {}
-    };
-
-    // Download the file to a temporary file to test on
-    let path = match util::download::download_temp(url).await {
-        Ok(response) => response.1,
+        Ok(_) => {},
         Err(err) => {
-            warn!("Failed to download image file, could not audit, assuming safe: {:?}", err);
-            return false;
-        }
+            warn!("Failed to read file metadata, could not audit, assuming safe: {}", err);
+            return None;
+        },
     };
 
-    // Test whether the image file is illegal
-    super::image::is_illegal_image(config, Arc::new(path)).await
+    // Do tests based on file type
+    match file_type {
+        FileType::Image => {
+            if let Some(verdict) = super::image::is_illegal_image(config, context, path).await {
+                return Some(verdict);
+            }
+        },
+        FileType::Video => {
+            #[cfg(feature = "ffmpeg")]
+            {
+                if let Some(verdict) = is_illegal_video(config, context, path).await {
+                    return Some(verdict);
+                }
+            }
+        },
+        FileType::AnimatedSticker => {
+            debug!("No scanner for animated Telegram stickers, assuming safe: {:?}", path);
+        },
+        FileType::Unknown => {
+            warn!("No scanners to audit file type, assuming safe: {:?}", path);
+        },
+    }
+
+    None
 }
 
-/// Check whether the given Telegram video is an illegal file.
+/// Check whether the video at the given path is illegal.
 #[cfg(feature = "ffmpeg")]
-async fn is_illegal_video(config: &Scanner, _: File, url: &Url) -> bool {
-    // Download the file to a temporary file to test on
-    let path = match util::download::download_temp(url).await {
-        Ok(response) => response.1,
-        Err(err) => {
-            warn!("Failed to download video file, could not audit, assuming safe: {:?}", err);
-            return false;
-        }
-    };
-
+async fn is_illegal_video(config: &Scanner, context: &Context, path: &Path) -> Option<Verdict> {
     // Extract video frames
-    let frame_file = match util::video::extract_frames(Arc::new(path)).await {
+    let frame_file = match util::video::extract_frames(path).await {
         Ok(frame_file) => frame_file,
         Err(_) => {
             warn!("Failed to extract video frames, could not audit, assuming safe");
-            return false;
+            return None;
         },
     };
 
     // Test whether the image file is illegal
-    super::image::is_illegal_image(config, frame_file).await
+    super::image::is_illegal_image(config, context, &frame_file)
+        .await
+        .map(|verdict| verdict.with_kind(Kind::Video))
 }
@@ -1,69 +1,86 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::sync::Arc;
 
 use dssim::Dssim;
-use futures::{future, prelude::*};
+use futures::{future, prelude::*, stream};
 use image::GenericImageView;
 use image::{imageops, FilterType};
-use tempfile::TempPath;
 
 #[cfg(feature = "ocr")]
 use crate::scanner;
 use crate::{
     config::{Image, Scanner},
-    // TODO: replace this
-    config::{AUDIT_IMAGE_COMPARE, IMAGE_CONCURRENT_MATCHES, IMAGE_MIN_SIZE},
-    util::{self, future::select_true},
+    util,
 };
+use super::score::select_verdicts;
+#[cfg(feature = "ocr")]
+use super::verdict::excerpt_around;
+use super::verdict::{Kind, Rule, Verdict};
+use super::Context;
 
-/// Check whether the given image is illegal.
-pub async fn is_illegal_image(config: &Scanner, path: Arc<TempPath>) -> bool {
+/// Check whether the image at the given path is illegal.
+#[cfg_attr(not(feature = "ocr"), allow(unused_variables))]
+pub async fn is_illegal_image(config: &Scanner, context: &Context, path: &Path) -> Option<Verdict> {
     debug!("Auditing image at '{:?}'...", path);
+    let path = Arc::new(path.to_path_buf());
 
-    let mut checks: Vec<Pin<Box<dyn Future<Output = bool> + Send>>> = vec![];
+    let mut checks: Vec<Pin<Box<dyn Future<Output = Option<Verdict>> + Send>>> = vec![];
 
     // Compare images against database of banned images
-    if AUDIT_IMAGE_COMPARE {
+    if config.image.compare {
         checks.push(matches_illegal_template(&config.image, path.clone()).boxed());
     }
 
     // Check for illegal text in images
     #[cfg(feature = "ocr")]
-    checks.push(has_illegal_text(config, path.clone()).boxed());
+    {
+        if config.image.ocr {
+            checks.push(has_illegal_text(config, context, path.clone()).boxed());
+        }
+    }
 
     // Run checks
-    select_true(checks).await
+    select_verdicts(&config.scoring, checks).await
 }
 
 /// Check whether the images contains any illegal text, with an OCR check.
 #[cfg(feature = "ocr")]
-async fn has_illegal_text(config: &Scanner, path: Arc<TempPath>) -> bool {
+async fn has_illegal_text(config: &Scanner, context: &Context, path: Arc<PathBuf>) -> Option<Verdict> {
     // Read text from image
     let text = match util::image::read_text(path).await {
         Ok(text) => text,
         Err(_) => {
             warn!("Failed to read text from image, could not audit, assuming safe");
-            return false;
+            return None;
         }
     };
 
-    // Trim and lowercase
-    let text = text.trim().to_lowercase();
+    // Trim and lowercase, keep the original case for checksummed cryptocurrency addresses
+    let text = text.trim();
+    let lowercase = text.to_lowercase();
 
     // Match the image against illegal image text
     let illegal = config
         .image
         .text
         .iter()
-        .any(|illegal_text| text.contains(&illegal_text.to_lowercase()));
-    if illegal {
+        .find(|illegal_text| lowercase.contains(&illegal_text.to_lowercase()));
+    if let Some(needle) = illegal {
         info!("Found illegal text in image");
-        return true;
+        return Some(Verdict::new(
+            Kind::Image,
+            Rule::ImageText {
+                needle: needle.clone(),
+                excerpt: excerpt_around(&lowercase, &needle.to_lowercase()),
+            },
+        ));
     }
 
     // Scan for generic illegal text as well, return the result
-    scanner::text::is_illegal_text(config, text).await
+    scanner::text::is_illegal_text(config, context, text.into())
+        .await
+        .map(|verdict| verdict.with_kind(Kind::Image))
 }
 
 /// Check whether an image matches an illegal image template.
@@ -71,32 +88,37 @@ async fn has_illegal_text(config: &Scanner, path: Arc<TempPath>) -> bool {
 /// This checks whether the image at the given path matches any of the images in the illegal image
 /// templates directory.
 ///
-/// True is returned if the image is illegal, false if not.
-/// On error, false is returned as it is assumed the image is allowed.
-async fn matches_illegal_template(config: &Image, path: Arc<TempPath>) -> bool {
-    // The image dir must be set
-    let image_dir = match config.dir {
-        Some(ref dir) => dir.clone(),
-        None => {
-            warn!("Attempt to audit image by matching, but not image directory is set");
-            return false;
-        }
-    };
+/// A verdict is returned if the image is illegal, `None` if not.
+/// On error, `None` is returned as it is assumed the image is allowed.
+async fn matches_illegal_template(config: &Image, path: Arc<PathBuf>) -> Option<Verdict> {
+    // An image dir must be set
+    let image_dirs: Vec<PathBuf> = config
+        .dir
+        .iter()
+        .map(PathBuf::from)
+        .chain(config.template_dirs.iter().cloned())
+        .collect();
+    if image_dirs.is_empty() {
+        warn!("Attempt to audit image by matching, but not image directory is set");
+        return None;
+    }
 
-    // Create a directory reader to list all image templates
-    let read_dir = match tokio::fs::read_dir(image_dir).await {
-        Ok(read_dir) => read_dir,
-        Err(err) => {
-            warn!(
-                "Failed to list illegal image templates, could not audit, assuming safe: {}",
+    // Create directory readers to list all image templates
+    let mut read_dirs = vec![];
+    for image_dir in image_dirs {
+        match tokio::fs::read_dir(&image_dir).await {
+            Ok(read_dir) => read_dirs.push(read_dir),
+            Err(err) => warn!(
+                "Failed to list illegal image templates in '{}', could not audit, assuming safe: {}",
+                image_dir.display(),
                 err
-            );
-            return false;
+            ),
         }
-    };
+    }
 
     // Test image for matches with templates, return on first match
-    read_dir
+    stream::iter(read_dirs)
+            .flatten()
             .filter_map(|template_path| {
                 future::ready(
                     template_path
@@ -112,45 +134,55 @@ async fn matches_illegal_template(config: &Image, path: Arc<TempPath>) -> bool {
                 let config = config.clone();
                 tokio_executor::blocking::run(move || match_image(config, path, template_path.path())).boxed()
             })
-            .buffer_unordered(*IMAGE_CONCURRENT_MATCHES)
-            .filter(|illegal| future::ready(*illegal))
+            .buffer_unordered(config.concurrent_matches)
+            .filter_map(future::ready)
             .next()
             .await
-            .is_some()
 }
 
 /// Check whether the images at the given two paths match.
 ///
 /// This operation is expensive.
-fn match_image(config: Image, path: Arc<TempPath>, template_path: PathBuf) -> bool {
+fn match_image(config: Image, path: Arc<PathBuf>, template_path: PathBuf) -> Option<Verdict> {
+    let template_name = template_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("")
+        .to_owned();
     debug!(
         "Matching illegal template '{}' against '{}'...",
-        template_path
-            .file_name()
-            .and_then(|name| name.to_str())
-            .unwrap_or(""),
+        template_name,
         path.to_str().unwrap_or("?"),
     );
 
     // Load the user image, return if it's too small
-    let image = match image::open(path.as_ref()) {
+    let image = match image::open(path.as_path()) {
         Ok(image) => image,
         Err(err) => {
             warn!(
                 "Failed to open image, could not audit, assuming safe: {}",
                 err
             );
-            return false;
+            return None;
         }
     };
     let (x, y) = image.dimensions();
-    if x < IMAGE_MIN_SIZE || y < IMAGE_MIN_SIZE {
+    if x < config.min_size || y < config.min_size {
         info!("Image too small to audit, assuming safe");
-        return false;
+        return None;
     }
 
     // Load the template image
-    let template_image = image::open(template_path).expect("failed to open base");
+    let template_image = match image::open(&template_path) {
+        Ok(image) => image,
+        Err(err) => {
+            warn!(
+                "Failed to open illegal image template '{}', skipping: {}",
+                template_name, err
+            );
+            return None;
+        }
+    };
 
     // Make the image we're testing the same size
     let (x, y) = template_image.dimensions();
@@ -172,14 +204,20 @@ fn match_image(config: Image, path: Arc<TempPath>, template_path: PathBuf) -> bo
     let score: f64 = result.0.into();
     let is_similar = score as f32 <= config.threshold;
 
-    if is_similar {
-        warn!(
-            "Found illegal image, matches banned template (score: {})",
-            score
-        );
-    } else {
+    if !is_similar {
         trace!("Matched image is legal (score: {})", score);
+        return None;
     }
 
-    is_similar
+    warn!(
+        "Found illegal image, matches banned template '{}' (score: {})",
+        template_name, score
+    );
+    Some(Verdict::new(
+        Kind::Image,
+        Rule::Template {
+            template: template_name,
+            score,
+        },
+    ))
 }
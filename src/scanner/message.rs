@@ -0,0 +1,49 @@
+use std::path::PathBuf;
+
+use futures::prelude::*;
+use url::Url;
+
+use crate::config::Scanner;
+use super::{score::select_verdicts, verdict::Verdict, Context};
+
+/// A message to audit.
+///
+/// This is a plain representation of a chat message, independent of the Telegram API.
+#[derive(Debug, Clone, Default)]
+pub struct Message {
+    /// The message text or caption.
+    pub text: Option<String>,
+
+    /// URLs hidden in the message text, such as text links.
+    pub hidden_urls: Vec<Url>,
+
+    /// Paths to files attached to the message, such as images and videos.
+    pub files: Vec<PathBuf>,
+}
+
+/// Check whether the given message is illegal.
+///
+/// This scans the message text, hidden URLs and attached files concurrently, adding up their
+/// scores. Returns a verdict describing why the message is illegal, or `None` if it is considered safe.
+pub async fn is_illegal_message(config: &Scanner, context: &Context, msg: &Message) -> Option<Verdict> {
+    let mut checks = vec![];
+
+    // Check message text
+    if let Some(text) = &msg.text {
+        // Scan any hidden URLs
+        if !msg.hidden_urls.is_empty() {
+            let urls = msg.hidden_urls.clone();
+            checks.push(super::url::any_illegal_url(&config.web, context, urls, 0).boxed());
+        }
+
+        // Scan the regular text
+        checks.push(super::text::is_illegal_text(config, context, text.clone()).boxed());
+    }
+
+    // Check message files (pictures, stickers, files, ...)
+    if !msg.files.is_empty() {
+        checks.push(super::file::has_illegal_files(config, context, &msg.files).boxed());
+    }
+
+    select_verdicts(&config.scoring, checks).await
+}
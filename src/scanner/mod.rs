@@ -1,4 +1,54 @@
+pub mod cache;
 pub mod file;
 pub mod image;
+pub mod message;
+pub mod score;
 pub mod text;
 pub mod url;
+pub mod verdict;
+
+pub use verdict::Verdict;
+
+use crate::{config::Config, util::http::Clients};
+use cache::UrlCache;
+
+/// Long-lived resources shared by all scanners.
+///
+/// Create it once and keep it for as long as the application runs, use `reconfigure` when the
+/// configuration changes. This is cheap to clone.
+#[derive(Clone)]
+pub struct Context {
+    /// HTTP clients to audit URLs and webpages with.
+    pub http: Clients,
+
+    /// Cache of URL audits.
+    pub urls: UrlCache,
+}
+
+impl Context {
+    /// Create the scanner context with the given configuration.
+    pub fn new(config: &Config) -> Self {
+        Self {
+            http: Clients::new(&config.http),
+            urls: UrlCache::new(&config.url_cache),
+        }
+    }
+
+    /// Rebuild the resources whose configuration differs between `old` and `config`.
+    ///
+    /// The URL cache is saved before it's replaced, so a cache kept at the same path keeps its
+    /// entries.
+    pub fn reconfigure(&mut self, old: &Config, config: &Config) {
+        if old.http != config.http {
+            info!("HTTP configuration changed, rebuilding HTTP clients");
+            self.http = Clients::new(&config.http);
+        }
+        if old.url_cache != config.url_cache {
+            info!("URL cache configuration changed, rebuilding URL cache");
+            if let Err(err) = self.urls.save() {
+                warn!("Failed to save URL cache before rebuilding it: {:?}", err);
+            }
+            self.urls = UrlCache::new(&config.url_cache);
+        }
+    }
+}
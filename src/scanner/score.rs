@@ -0,0 +1,32 @@
+use futures::{prelude::*, stream::FuturesUnordered};
+
+use crate::config::Scoring;
+use super::verdict::Verdict;
+
+/// Run the given checks concurrently, and combine their verdicts.
+///
+/// The score of all verdicts adds up. This returns as soon as the ban threshold is reached, or
+/// when all checks are complete. Returns `None` if no check yields a verdict.
+///
+/// See: `util::future::select_some`, which returns on the first verdict instead.
+pub async fn select_verdicts<I>(config: &Scoring, checks: I) -> Option<Verdict>
+where
+    I: IntoIterator,
+    I::Item: Future<Output = Option<Verdict>>,
+{
+    let mut checks: FuturesUnordered<_> = checks.into_iter().collect();
+    let mut verdicts = vec![];
+    let mut score = 0.0;
+
+    while let Some(verdict) = checks.next().await {
+        if let Some(verdict) = verdict {
+            score += config.score(&verdict);
+            verdicts.push(verdict);
+            if score >= config.thresholds.ban {
+                break;
+            }
+        }
+    }
+
+    Verdict::combine(verdicts)
+}
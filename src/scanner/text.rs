@@ -1,129 +1,245 @@
-use crate::config::{Scanner, Text};
+use crate::{
+    config::{Address, Classifier, Scanner, Scoring, Text},
+    util::{address::find_addresses, text::skeleton},
+};
+use super::{
+    verdict::{Kind, Rule, Verdict},
+    Context,
+};
 
 /// Check whether the given text is illegal.
-pub async fn is_illegal_text(config: &Scanner, text: String) -> bool {
-    // Check for illegal text
-    if matches_illegal_text(&config.text, &text) {
-        return true;
+///
+/// This checks for illegal text, suspicious phrases, cryptocurrency addresses and the classifier,
+/// and for illegal URLs unless the text alone reaches the ban threshold.
+pub async fn is_illegal_text(config: &Scanner, context: &Context, text: String) -> Option<Verdict> {
+    // Check for illegal text, suspicious phrases and addresses, and classify the text
+    let mut verdicts: Vec<Verdict> = matches_illegal_text(&config.text, &text)
+        .into_iter()
+        .chain(matches_phrases(&config.scoring, &text))
+        .chain(matches_addresses(&config.address, &text))
+        .chain(matches_classifier(&config.classifier, &text))
+        .collect();
+    let score: f64 = verdicts.iter().map(|verdict| config.scoring.score(verdict)).sum();
+    if score >= config.scoring.thresholds.ban {
+        return Verdict::combine(verdicts);
     }
 
     // Check for illegal URLs
-    super::url::contains_illegal_urls(&config.web, &text).await
+    verdicts.extend(super::url::contains_illegal_urls(&config.web, context, &text).await);
+    Verdict::combine(verdicts)
 }
 
 /// Check whether the text contains illegal parts.
-pub fn matches_illegal_text(config: &Text, text: &str) -> bool {
+pub fn matches_illegal_text(config: &Text, text: &str) -> Option<Verdict> {
     // We must have illegal text configured
     if config.text.is_empty() {
-        return false;
+        return None;
     }
 
-    // Normalize the text
-    let text = text.trim().to_lowercase();
+    // Normalize the text into its skeleton, undoing obfuscation
+    let text = skeleton(text);
 
-    // Match ASCII parts against banned text
-    if config.text
-        .iter()
-        .any(|illegal| contains_smart(&text, illegal))
-    {
-        warn!("Found illegal text");
-        return true;
-    }
+    // Match the skeleton against banned text
+    let illegal = &config.text[config.matcher.find(text.as_bytes())?];
+    warn!("Found illegal text (matched: {:?})", illegal.as_str());
 
-    false
+    Some(Verdict::new(Kind::Text, Rule::Text { needle: illegal.as_str().into() }))
 }
 
-/// Smart check whehter two strings match.
+/// Check whether the text contains suspicious phrases.
 ///
-/// This compares characters, ignoring case.
-/// All non ASCII chars are considered to be equal, to bypass text obfuscation.
+/// Returns a verdict with all phrases that are found, scored with the sum of their weights.
+pub fn matches_phrases(config: &Scoring, text: &str) -> Option<Verdict> {
+    if config.phrases.is_empty() {
+        return None;
+    }
+
+    // Match the skeleton against all phrases
+    let found = config.matcher.find_all(skeleton(text).as_bytes());
+    if found.is_empty() {
+        return None;
+    }
+    let phrases: Vec<_> = found.into_iter().map(|i| &config.phrases[i]).collect();
+    let score = phrases.iter().map(|phrase| phrase.weight).sum();
+    debug!("Found suspicious phrases (score: {})", score);
+
+    Some(Verdict::new(
+        Kind::Text,
+        Rule::Phrases {
+            phrases: phrases.iter().map(|phrase| phrase.rule.as_str().into()).collect(),
+            score,
+        },
+    ))
+}
+
+/// Check whether the text contains illegal cryptocurrency addresses, or giveaway wording along with
+/// any address.
 ///
-/// The `contains` value should only contain ASCII characters.
-// TODO: find a better way of doing this, this issued a faulty audit in the past
-fn contains_smart(text: &str, contains: &str) -> bool {
-    // Both must not be empty, contains must not be shorter
-    if text.is_empty() || contains.is_empty() || text.len() < contains.len() {
-        return false;
-    }
-
-    // Get haystack and needle length in characters, skip if haystack not big enough
-    let text_len = text.chars().count();
-    let contains_len = contains.chars().count();
-    if text_len < contains_len {
-        return false;
-    }
-
-    // Compare
-    let contains_first = contains.chars().next().unwrap();
-    text
-        .chars()
-        .take(text_len - contains_len + 1)
-        .enumerate()
-        .filter(|(_, c)| char_matches_smart(*c, contains_first))
-        .any(|(i, _)| {
-            // Define set to scan
-            let set = text.chars().skip(i).take(contains_len);
-
-            // Match all ASCII characters, and ensure at least 20% is ASCII
-            set.clone()
-                .zip(contains.chars())
-                .all(|(a, b)| char_matches_smart(a, b))
-            && {
-                // At least 20% and at least 4 must be ASCII to be valid
-                let set_len = set.clone().filter(|c| !c.is_whitespace()).count();
-                let min_ascii = (1 + set_len / 5).max(set_len.min(4));
-                set.filter(|c| c.is_ascii() && !c.is_whitespace()).count() >= min_ascii
-            }
-        })
+/// Only addresses with a valid checksum are considered.
+pub fn matches_addresses(config: &Address, text: &str) -> Option<Verdict> {
+    if config.blocked.is_empty() && config.giveaway.is_empty() {
+        return None;
+    }
+    let addresses = find_addresses(text);
+    if addresses.is_empty() {
+        return None;
+    }
+    let mut verdicts = vec![];
+
+    // Match addresses against the blocklist
+    let blocked = addresses
+        .iter()
+        .find(|address| config.blocked.contains(&address.normalized()));
+    if let Some(address) = blocked {
+        warn!("Found illegal address (matched: {:?})", address.address);
+        verdicts.push(Verdict::new(
+            Kind::Text,
+            Rule::Address {
+                address: address.address.clone(),
+            },
+        ));
+    }
+
+    // Match giveaway wording along with any address
+    if let Some(i) = config.giveaway_matcher.find(skeleton(text).as_bytes()) {
+        debug!("Found giveaway text along with an address");
+        verdicts.push(Verdict::new(
+            Kind::Text,
+            Rule::GiveawayAddress {
+                needle: config.giveaway[i].as_str().into(),
+                address: addresses[0].address.clone(),
+            },
+        ));
+    }
+
+    Verdict::combine(verdicts)
 }
 
-/// Smart check whehter a char matches.
+/// Check whether the text is classified as spam.
 ///
-/// This compares characters, ignoring case.
-/// All non ASCII chars are considered to be equal, to bypass text obfuscation.
-#[inline]
-fn char_matches_smart(a: char, b: char) -> bool {
-    a == b || !a.is_ascii() || !b.is_ascii() || a.to_ascii_uppercase() == b.to_ascii_uppercase()
+/// Returns a verdict if the spam probability reaches the classifier threshold.
+pub fn matches_classifier(config: &Classifier, text: &str) -> Option<Verdict> {
+    let model = config.loaded.as_ref()?;
+    let probability = model.probability(text);
+    if probability < config.threshold {
+        return None;
+    }
+    debug!("Text classified as spam (probability: {})", probability);
+
+    Some(Verdict::new(Kind::Text, Rule::Classifier { probability }))
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
     use super::*;
+    use crate::{
+        classifier::Model,
+        config::{matcher::Matcher, pattern::Pattern},
+    };
+
+    #[test]
+    fn test_matches_illegal_regex() {
+        let text = vec![Pattern::regex(r"b[i1]nance\s+us\b", "").unwrap()];
+        let config = Text {
+            matcher: Matcher::new(&text, skeleton),
+            text,
+        };
+        assert!(matches_illegal_text(&config, "Celebrating Bἰⴖаnce US").is_some());
+        assert!(matches_illegal_text(&config, "BINANCE USA").is_none());
+        assert!(matches_illegal_text(&config, "Oh ja tuurlijk, sancties.").is_none());
+    }
 
     #[test]
-    fn test_contains_smart() {
-        assert!(contains_smart("a", "a"));
-        assert!(contains_smart("aa", "a"));
-        assert!(contains_smart("aaaaaaa", "a"));
-        assert!(contains_smart("aaaaaaa", "aaa"));
-        assert!(!contains_smart("a", "aa"));
-        assert!(contains_smart("abcdefg", "c"));
-        assert!(contains_smart("abcdefg", "bc"));
-        assert!(contains_smart("abcdefg", "g"));
-        assert!(!contains_smart("     ", " "));
-
-        assert!(!contains_smart("éééééééééééééééééééé", "e"));
-        assert!(!contains_smart("éééééééééééééééééééé", "é"));
-        assert!(contains_smart("éééééeeééééééééééééé", "eé"));
-        assert!(!contains_smart("éééééabcéééééééé", "abcdefghijkl"));
-        assert!(contains_smart("éééééabcdéééééééé", "abcdefghijkl"));
-        assert!(!contains_smart("éééééabcdéééééééé", "abcdefghijklm"));
-        assert!(!contains_smart("éééééabcdéééééééé", "bcdefghijkl"));
-        assert!(contains_smart("thís ís sómé tést", "this is some test"));
-        assert!(!contains_smart("thís ís sómé tést", "this"));
-
-        assert!(contains_smart("Celebrating our new crypto exchange", "Celebrating Our New Crypto Exchange"));
-        assert!(!contains_smart("Celebrating our old crypto exchange", "Celebrating Our New Crypto Exchange"));
-
-        assert!(contains_smart("Вinаⴖce US", "Binance US"));
-        assert!(contains_smart("Βἱnаⴖcе US", "Binance US"));
-        assert!(contains_smart("𐌉МΡOR𐌕АΝΤAA", "IMPORTANTAA"));
-        assert!(contains_smart("ⵏMР𐩒RΤΑNТAA", "IMPORTANTAA"));
-        assert!(!contains_smart("𐌉МΡOR𐌕АΝΤA", "IMPORTANTA"));
-        assert!(contains_smart("ⴹνеⴖt еnd𐑈 tоԁау!", "Event ends today!"));
-        assert!(contains_smart("Ενеⴖt ends tоԁаγ!", "Event ends today!"));
-        assert!(contains_smart("𐌏ⴖƖγ thе fіr𐑈t 5000 u𐑈егs wἱƖƖ be гewardеd", "Only the first 5000 users will be rewarded"));
-        assert!(contains_smart("OnƖy the fἰгѕt 5000 u𐑈егѕ ԝіlƖ ƅe reԝаrdеd", "Only the first 5000 users will be rewarded"));
+    fn test_matches_addresses() {
+        let mut scanner: Scanner = toml::from_str(
+            r#"
+            text = { text = [] }
+            web = { hosts = [], host_parts = [], text = [] }
+            image = { threshold = 0.5, text = [] }
+            [address]
+            blocklist = ["0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed"]
+            giveaway = ["send", { regex = '\bgiveaway\b' }]
+            "#,
+        )
+        .unwrap();
+        scanner.compile();
+        let config = &scanner.address;
+        let rule = |text: &str| matches_addresses(config, text).map(|verdict| verdict.rule);
+
+        match rule("Deposit to 0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed now") {
+            Some(Rule::Address { address }) => assert!(address.starts_with("0x5aAeb")),
+            rule => panic!("unexpected rule {:?}", rule),
+        }
+        match rule("BTC Gіveaway! 1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa") {
+            Some(Rule::GiveawayAddress { needle, .. }) => assert_eq!(needle, r"/\bgiveaway\b/"),
+            rule => panic!("unexpected rule {:?}", rule),
+        }
+        match rule("Send 1 ETH to 0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed") {
+            Some(Rule::Combined { verdicts }) => assert_eq!(verdicts.len(), 2),
+            rule => panic!("unexpected rule {:?}", rule),
+        }
+
+        // Invalid checksums and giveaway wording alone are not matched
+        assert!(rule("Send to 0x5aAeb6053f3E94C9b9A09f33669435E7Ef1BeAed").is_none());
+        assert!(rule("Our giveaway starts tomorrow").is_none());
+    }
+
+    #[test]
+    fn test_matches_classifier() {
+        let samples = vec![
+            ("Deposit now, bonus for the first 5000 participants", true),
+            ("Good morning everyone", false),
+        ];
+        let model = Model::train(2, samples).unwrap();
+        let mut config = Classifier::default();
+        assert!(matches_classifier(&config, "Deposit bonus").is_none());
+
+        config.loaded = Some(Arc::new(model));
+        assert!(matches_classifier(&config, "Bonus for the first 5000 participants").is_some());
+        assert!(matches_classifier(&config, "Good morning").is_none());
+    }
+
+    /// Check whether the skeleton of the text contains the needle.
+    fn contains(text: &str, needle: &str) -> bool {
+        let needles = vec![Pattern::Literal(needle.into())];
+        let config = Text {
+            matcher: Matcher::new(&needles, skeleton),
+            text: needles,
+        };
+        matches_illegal_text(&config, text).is_some()
+    }
+
+    #[test]
+    fn test_contains_skeleton() {
+        assert!(contains("a", "a"));
+        assert!(contains("aa", "a"));
+        assert!(contains("aaaaaaa", "a"));
+        assert!(contains("aaaaaaa", "aaa"));
+        assert!(!contains("a", "aa"));
+        assert!(contains("abcdefg", "c"));
+        assert!(contains("abcdefg", "bc"));
+        assert!(contains("abcdefg", "g"));
+        assert!(!contains("     ", " "));
+
+        assert!(contains("éééééeeééééééééééééé", "eé"));
+        assert!(!contains("éééééabcéééééééé", "abcdefghijkl"));
+        assert!(!contains("éééééabcdéééééééé", "abcdefghijklm"));
+        assert!(!contains("éééééabcdéééééééé", "bcdefghijkl"));
+        assert!(contains("thís ís sómé tést", "this is some test"));
+
+        assert!(contains("Celebrating our new crypto exchange", "Celebrating Our New Crypto Exchange"));
+        assert!(!contains("Celebrating our old crypto exchange", "Celebrating Our New Crypto Exchange"));
+
+        assert!(contains("Вinаⴖce US", "Binance US"));
+        assert!(contains("Βἱnаⴖcе US", "Binance US"));
+        assert!(contains("𐌉МΡOR𐌕АΝΤAA", "IMPORTANTAA"));
+        assert!(contains("ⵏMР𐩒RΤΑNТAA", "IMPORTANTAA"));
+        assert!(contains("ⴹνеⴖt еnd𐑈 tоԁау!", "Event ends today!"));
+        assert!(contains("Ενеⴖt ends tоԁаγ!", "Event ends today!"));
+        assert!(contains("𐌏ⴖƖγ thе fіr𐑈t 5000 u𐑈егs wἱƖƖ be гewardеd", "Only the first 5000 users will be rewarded"));
+        assert!(contains("OnƖy the fἰгѕt 5000 u𐑈егѕ ԝіlƖ ƅe reԝаrdеd", "Only the first 5000 users will be rewarded"));
 
         // Test a real sample
         let sample = "Cеlеƅгаtἰng Our Νeԝ Сrуpto Еxchаⴖge - Bἰⴖаnce US\n\
@@ -148,20 +264,43 @@ mod tests {
             \n\
             Βiⴖаnсе US\
         ";
-        assert!(contains_smart(sample, "Celebrating Our New Crypto Exchange"));
-        assert!(!contains_smart(sample, "Celebrating Our New Crypto Exchange nope"));
-        assert!(contains_smart(sample, "Binance is pleased to announce the unmatched trading"));
-        assert!(!contains_smart(sample, "Binance is pleased to announce the unmatched trading nope"));
-        assert!(contains_smart(sample, "To celebrate the launch of Binance US"));
-        assert!(!contains_smart(sample, "To celebrate the launch of Binance US nope"));
-        assert!(contains_smart(sample, "Event ends today!"));
-        assert!(!contains_smart(sample, "Event ends today! nope"));
-        assert!(contains_smart(sample, "First 5000 Participants"));
-        assert!(!contains_smart(sample, "First 5000 Participants Bonus"));
-        assert!(contains_smart(sample, "Only the first 5000 users will be rewarded"));
-        assert!(!contains_smart(sample, "Only the first 5000 users will be rewarded nope"));
+        assert!(contains(sample, "Celebrating Our New Crypto Exchange"));
+        assert!(!contains(sample, "Celebrating Our New Crypto Exchange nope"));
+        assert!(contains(sample, "Binance is pleased to announce the unmatched trading"));
+        assert!(!contains(sample, "Binance is pleased to announce the unmatched trading nope"));
+        assert!(contains(sample, "To celebrate the launch of Binance US"));
+        assert!(!contains(sample, "To celebrate the launch of Binance US nope"));
+        assert!(contains(sample, "Event ends today!"));
+        assert!(!contains(sample, "Event ends today! nope"));
+        assert!(contains(sample, "First 5000 Participants"));
+        assert!(!contains(sample, "First 5000 Participants Bonus"));
+        assert!(contains(sample, "Only the first 5000 users will be rewarded"));
+        assert!(!contains(sample, "Only the first 5000 users will be rewarded nope"));
 
         // Historical false positives
-        assert!(!contains_smart("Oh ja tuurlijk, sancties. 🤦🏻‍♂️🤦🏻‍♂️🤦🏻‍♂️🤦🏻‍♂️", "Celebrating Our New Crypto Exchange"));
+        assert!(!contains("Oh ja tuurlijk, sancties. 🤦🏻‍♂️🤦🏻‍♂️🤦🏻‍♂️🤦🏻‍♂️", "Celebrating Our New Crypto Exchange"));
+    }
+
+    #[test]
+    fn test_contains_skeleton_changes() {
+        // Diacritics are stripped, instead of making the character a wildcard
+        assert!(contains("éééééééééééééééééééé", "e"));
+        assert!(contains("éééééééééééééééééééé", "é"));
+        assert!(contains("thís ís sómé tést", "this"));
+
+        // Unknown characters are not wildcards, so they can't fill in for missing text
+        assert!(!contains("éééééabcdéééééééé", "abcdefghijkl"));
+
+        // Lookalikes match regardless of how many ASCII characters are left
+        assert!(contains("𐌉МΡOR𐌕АΝΤA", "IMPORTANTA"));
+    }
+
+    #[test]
+    fn test_contains_lookalikes() {
+        // Other characters are not wildcards, and invisible characters are stripped
+        assert!(!contains("Вживання нової біржі", "Binance"));
+        assert!(!contains("币安交易所", "Binance"));
+        assert!(contains("Bi\u{200B}nance U\u{200D}S", "Binance US"));
+        assert!(contains("Binance\u{202E} \u{00A0} US", "Binance US"));
     }
 }
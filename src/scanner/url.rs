@@ -1,42 +1,81 @@
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use futures::future::{BoxFuture, FutureExt};
-use reqwest::{header, r#async::Client, RedirectPolicy};
+use reqwest::header;
 use url::Url;
 
 use crate::{
-    config::*,
-    util::{self, future::select_true, url::find_page_urls},
+    config::{pattern::Pattern, *},
+    util::{self, future::select_some, url::find_page_urls},
+};
+use super::{
+    verdict::{Kind, Rule, Verdict},
+    Context,
 };
 
 /// Check whether the given text contains any illegal URLs.
 ///
 /// This uses `ILLEGAL_HOSTS`.
-pub async fn contains_illegal_urls(config: &Web, text: &str) -> bool {
+pub async fn contains_illegal_urls(config: &Web, context: &Context, text: &str) -> Option<Verdict> {
     // Find URLs in the message, return if there are none
     let urls = util::url::find_urls(text);
     if urls.is_empty() {
-        return false;
+        return None;
     }
 
-    any_illegal_url(config, urls, 0).await
+    any_illegal_url(config, context, urls, 0).await
 }
 
 /// Check whether the given list of URLs contains any illegal URL.
 ///
 /// This uses `ILLEGAL_HOSTS`.
-pub fn any_illegal_url<'a, I>(config: &'a Web, urls: I, depth: usize) -> BoxFuture<'a, bool>
+pub fn any_illegal_url<'a, I>(
+    config: &'a Web,
+    context: &'a Context,
+    urls: I,
+    depth: usize,
+) -> BoxFuture<'a, Option<Verdict>>
+where
+    I: IntoIterator<Item = Url> + Send + 'a,
+    I::IntoIter: Send,
+{
+    audit_urls(config, context, urls, depth)
+        .map(|verdict| verdict.unwrap_or(None))
+        .boxed()
+}
+
+/// Check whether the given list of URLs contains any illegal URL.
+///
+/// Returns an error if no illegal URL was found, but not all URLs could be audited.
+fn audit_urls<'a, I>(
+    config: &'a Web,
+    context: &'a Context,
+    urls: I,
+    depth: usize,
+) -> BoxFuture<'a, Result<Option<Verdict>, ()>>
 where
     I: IntoIterator<Item = Url> + Send + 'a,
     I::IntoIter: Send,
 {
     async move {
-        // Test each URL concurrently
-        select_true(
-            urls.into_iter()
-                .map(|url| is_illegal_url(config, url, depth)),
-        )
-        .await
+        // Test each URL concurrently, remember whether any audit failed
+        let failed = AtomicBool::new(false);
+        let verdict = select_some(urls.into_iter().map(|url| {
+            let failed = &failed;
+            is_illegal_url(config, context, url, depth).map(move |verdict| {
+                verdict.unwrap_or_else(|()| {
+                    failed.store(true, Ordering::Relaxed);
+                    None
+                })
+            })
+        }))
+        .await;
+
+        match verdict {
+            Some(verdict) => Ok(Some(verdict)),
+            None if failed.load(Ordering::Relaxed) => Err(()),
+            None => Ok(None),
+        }
     }
     .boxed()
 }
@@ -45,69 +84,113 @@ where
 ///
 /// This compares the given URL, and the URL it possibly redirects to.
 ///
-/// Returns `Ok` if the URL is illegal, `Err` otherwise.
-/// Errors are silently dropped and it will then be assumed that the URL is allowed.
-/// This allows the use of `futures::future::select_ok`.
-async fn is_illegal_url(config: &Web, mut url: Url, depth: usize) -> bool {
+/// Returns `Some` with a verdict if the URL is illegal, `None` otherwise.
+/// Returns an error if the URL could not be fully audited, it may then be assumed to be allowed.
+///
+/// Audits are cached, a cached URL is audited without any network requests. Hosts are always
+/// checked against the current rules. If the web rules changed since a URL was audited, only the
+/// URL its redirects lead to is used, and its webpage is audited again.
+async fn is_illegal_url(
+    config: &Web,
+    context: &Context,
+    url: Url,
+    depth: usize,
+) -> Result<Option<Verdict>, ()> {
     // The given URL must not be illegal
-    if is_illegal_static_url(config, &url) {
-        return true;
+    if let Some(verdict) = is_illegal_static_url(config, &url) {
+        return Ok(Some(verdict));
     }
 
-    // Follow URL redirects
-    match util::url::follow_url(&url).await {
-        Ok(ref url) if is_illegal_static_url(config, url) => return true,
-        Ok(new) => url = new,
-        Err(err) => debug!(
-            "Failed to follow URL redirects, could not audit, assuming safe: {:?}",
-            err
-        ),
-    }
+    // Use the cached audit if known, keep track of the URL chain
+    let chain = match context.urls.get(&url) {
+        Some(entry) => {
+            let chain = vec![url, entry.target];
+            if let Some(verdict) = is_illegal_static_url(config, &chain[1]) {
+                return Ok(Some(verdict.via(&chain[..1])));
+            }
+            if entry.revision == config.revision {
+                debug!("Using cached audit of URL: {}", chain[0]);
+                return Ok(entry.verdict.map(|verdict| verdict.via(&chain)));
+            }
+            debug!("Web rules changed since cached audit, auditing webpage again: {}", chain[0]);
+            chain
+        }
+        None => {
+            // Follow URL redirects
+            let mut chain = vec![url];
+            match util::url::follow_url(&context.http, &chain[0]).await {
+                Ok(new) => {
+                    if let Some(verdict) = is_illegal_static_url(config, &new) {
+                        return Ok(Some(verdict.via(&chain)));
+                    }
+                    chain.push(new);
+                }
+                Err(err) => debug!(
+                    "Failed to follow URL redirects, could not audit, assuming safe: {:?}",
+                    err
+                ),
+            }
+            chain
+        }
+    };
 
-    // Check whether the webpage contains illegal content
-    if url_has_illegal_webpage_content(config, &url, depth).await {
-        warn!("Found illegal URL, webpage has illegal content: {}", url);
-        return true;
+    // Check whether the webpage contains illegal content, cache the audit if it fully completed
+    let url = chain.last().unwrap();
+    let verdict = url_has_illegal_webpage_content(config, context, url, depth).await;
+    if chain.len() > 1 {
+        match &verdict {
+            // Nested URLs may not have been scanned below the top level, don't cache as legal
+            Ok(None) if depth > 0 => {}
+            Ok(verdict) => {
+                context
+                    .urls
+                    .insert(&chain[0], url.clone(), verdict.clone(), config.revision)
+            }
+            Err(()) => {}
+        }
     }
 
-    false
+    let verdict = match verdict? {
+        Some(verdict) => verdict,
+        None => return Ok(None),
+    };
+    warn!("Found illegal URL, webpage has illegal content: {}", url);
+    Ok(Some(verdict.via(&chain)))
 }
 
 /// Check whether the given URL routes to illegal content.
 ///
 /// This scans the body of the webpage that is responded with.
-async fn url_has_illegal_webpage_content(config: &Web, url: &Url, depth: usize) -> bool {
+///
+/// Returns an error if the webpage, or any URL on it, could not be audited, in which case it is
+/// assumed to be safe.
+async fn url_has_illegal_webpage_content(
+    config: &Web,
+    context: &Context,
+    url: &Url,
+    depth: usize,
+) -> Result<Option<Verdict>, ()> {
     // We must have illegal webpage text configured
     if config.text.is_empty() {
-        return false;
+        return Ok(None);
     }
 
-    // Build the URL client
-    // TODO: use a global client instance
-    let mut headers = header::HeaderMap::new();
-    headers.insert(
-        header::COOKIE,
-        header::HeaderValue::from_static("__test=bda194efef091b052793e3eb74b1b952; id=185"),
-    );
-    let client = Client::builder()
-        .danger_accept_invalid_certs(true)
-        .redirect(RedirectPolicy::limited(25))
-        .timeout(Duration::from_secs(15))
-        .connect_timeout(Duration::from_secs(20))
-        .default_headers(headers)
-        .build()
-        .expect("failed to build webpage body auditer client");
-
     // Send the request, follow the URL
     // TODO: validate status !response.status.is_success()
-    let response = match client.get(url.as_str()).send().await {
+    let permit = context.http.permit(url).await;
+    let request = context
+        .http
+        .audit()
+        .get(url.as_str())
+        .header(header::COOKIE, "__test=bda194efef091b052793e3eb74b1b952; id=185");
+    let response = match request.send().await {
         Ok(response) => response,
         Err(err) => {
             debug!(
                 "Failed to request webpage content, could not audit, assuming safe: {}",
                 err
             );
-            return false;
+            return Err(());
         }
     };
 
@@ -119,45 +202,27 @@ async fn url_has_illegal_webpage_content(config: &Web, url: &Url, depth: usize)
                 "Failed to receive webpage content, could not audit, assuming safe: {}",
                 err
             );
-            return false;
+            return Err(());
         }
     };
+    drop(permit);
 
-    // Find the shortest needle to limit body searching
-    let needles = &config.text;
-    let shortest = needles.iter().map(|t| t.len()).min().unwrap_or(0);
-
-    // The body must be long enough
-    if body.len() < shortest {
-        debug!("Webpage body is too small to scan");
-        return false;
-    }
-
-    // Scan body for needles to detect illegal content
-    let has_illegal_text = (0..=body.len() - shortest).any(|i| {
-        needles
-            .iter()
-            .filter(|needle| needle.as_bytes().len() <= body.len() - i)
-            .any(|needle| {
-                if &body[i..i + needle.len()] == needle.as_bytes() {
-                    warn!(
-                        "Webpage content matched (matched: {:?})",
-                        needle.chars().take(32).collect::<String>()
-                    );
-                    true
-                } else {
-                    false
-                }
-            })
-    });
-    if has_illegal_text {
-        return true;
+    // Scan body for needles to detect illegal content, case-insensitively like other rules
+    let text = String::from_utf8_lossy(&body).to_lowercase();
+    if let Some(i) = config.text_matcher.find(text.as_bytes()) {
+        let needle = config.text[i].as_str();
+        warn!(
+            "Webpage content matched (matched: {:?})",
+            needle.chars().take(32).collect::<String>()
+        );
+        return Ok(Some(Verdict::new(Kind::Url, Rule::Webpage { needle: needle.into() })));
     }
 
     // Audit any sketchy URLs from the webpage body as well
-    if depth < MAX_DEPTH {
-        if any_illegal_url(config, find_page_urls(&body), depth + 1).await {
-            return true;
+    if depth < config.max_depth {
+        let urls = find_page_urls(&body, &config.scan_hosts);
+        if let Some(verdict) = audit_urls(config, context, urls, depth + 1).await? {
+            return Ok(Some(verdict));
         }
     } else {
         warn!(
@@ -166,45 +231,46 @@ async fn url_has_illegal_webpage_content(config: &Web, url: &Url, depth: usize)
         );
     }
 
-    false
+    Ok(None)
 }
 
 /// Check wheher the given URL is illegal.
 ///
 /// This checks the static URL, and does not do any redirect checking.
-pub fn is_illegal_static_url(config: &Web, url: &Url) -> bool {
+pub fn is_illegal_static_url(config: &Web, url: &Url) -> Option<Verdict> {
     // We must have illegal hosts or parts configured
     if config.hosts.is_empty() && config.host_parts.is_empty() {
-        return false;
+        return None;
     }
 
     // Get the host
-    let host = match url.host_str() {
-        Some(host) => host,
-        None => return false,
-    };
-    let host = host.trim().to_lowercase();
+    let host = url.host_str()?.trim().to_lowercase();
 
     // Match the URL against a list of banned hosts
-    if config
-        .hosts
-        .iter()
-        .any(|illegal_host| illegal_host == &host)
-    {
+    if config.hosts.iter().any(|illegal_host| match illegal_host {
+        Pattern::Literal(illegal_host) => illegal_host == &host,
+        Pattern::Regex { regex, .. } => regex.is_match(&host),
+    }) {
         warn!("Found illegal host: {}", url);
-        return true;
+        return Some(Verdict::new(Kind::Url, Rule::Host { host }).via(&[url.clone()]));
     }
 
     // Match the URL against a list of banned host parts
     let illegal = config
         .host_parts
         .iter()
-        .any(|illegal_part| host.contains(illegal_part));
-    if illegal {
+        .find(|illegal_part| match illegal_part {
+            Pattern::Literal(part) => host.contains(part.as_str()),
+            Pattern::Regex { regex, .. } => regex.is_match(&host),
+        });
+    if let Some(part) = illegal {
         warn!("Found illegal host (contains illegal part): {}", url);
-        return true;
+        return Some(
+            Verdict::new(Kind::Url, Rule::HostPart { host, part: part.as_str().into() })
+                .via(&[url.clone()]),
+        );
     }
 
     debug!("Audited URL as safe: {}", url);
-    false
+    None
 }
@@ -0,0 +1,230 @@
+use std::fmt;
+
+use url::Url;
+
+/// Maximum number of characters to keep for text excerpts in a verdict.
+const EXCERPT_LEN: usize = 64;
+
+/// A verdict of a scanner, describing why content is considered illegal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Verdict {
+    /// The kind of scanner that produced this verdict.
+    pub kind: Kind,
+
+    /// The rule that matched.
+    pub rule: Rule,
+
+    /// The chain of URLs that was followed to find the illegal content.
+    ///
+    /// This is empty if no URLs were involved.
+    pub urls: Vec<Url>,
+}
+
+impl Verdict {
+    /// Construct a new verdict for the given scanner kind and matched rule.
+    pub fn new(kind: Kind, rule: Rule) -> Self {
+        Self {
+            kind,
+            rule,
+            urls: vec![],
+        }
+    }
+
+    /// Change the scanner kind of this verdict.
+    ///
+    /// Used when a scanner delegates to another scanner, such as text found in an image.
+    pub fn with_kind(mut self, kind: Kind) -> Self {
+        self.kind = kind;
+        if let Rule::Combined { verdicts } = self.rule {
+            self.rule = Rule::Combined {
+                verdicts: verdicts.into_iter().map(|verdict| verdict.with_kind(kind)).collect(),
+            };
+        }
+        self
+    }
+
+    /// Combine the given verdicts into one, whose score is the sum of all.
+    ///
+    /// Returns `None` if there are no verdicts, and a single verdict as is.
+    pub fn combine(verdicts: Vec<Verdict>) -> Option<Self> {
+        // Flatten verdicts that are combined already
+        let mut verdicts: Vec<Verdict> = verdicts
+            .into_iter()
+            .flat_map(|verdict| match verdict.rule {
+                Rule::Combined { verdicts } => verdicts,
+                _ => vec![verdict],
+            })
+            .collect();
+
+        match verdicts.len() {
+            0 => None,
+            1 => verdicts.pop(),
+            _ => Some(Self::new(verdicts[0].kind, Rule::Combined { verdicts })),
+        }
+    }
+
+    /// Prepend the given URL chain to the chain of this verdict.
+    pub fn via(mut self, chain: &[Url]) -> Self {
+        self.urls.splice(0..0, chain.iter().cloned());
+        self.urls.dedup();
+        self
+    }
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.rule {
+            Rule::Combined { verdicts } => {
+                let verdicts: Vec<String> = verdicts.iter().map(|v| v.to_string()).collect();
+                write!(f, "{}", verdicts.join("; "))?;
+            }
+            rule => write!(f, "{} scanner, {}", self.kind, rule)?,
+        }
+
+        if !self.urls.is_empty() {
+            let chain: Vec<&str> = self.urls.iter().map(|url| url.as_str()).collect();
+            write!(f, " (via {})", chain.join(" -> "))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The kind of scanner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    /// Message text scanner.
+    Text,
+
+    /// URL and webpage scanner.
+    Url,
+
+    /// Image scanner.
+    Image,
+
+    /// Video scanner.
+    Video,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Kind::Text => write!(f, "text"),
+            Kind::Url => write!(f, "URL"),
+            Kind::Image => write!(f, "image"),
+            Kind::Video => write!(f, "video"),
+        }
+    }
+}
+
+/// A rule that matched illegal content.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Rule {
+    /// Text contains an illegal needle.
+    Text { needle: String },
+
+    /// Text contains suspicious phrases, with the sum of their weights.
+    Phrases { phrases: Vec<String>, score: f64 },
+
+    /// URL has an illegal host.
+    Host { host: String },
+
+    /// URL host contains an illegal host part.
+    HostPart { host: String, part: String },
+
+    /// Webpage body contains an illegal needle.
+    Webpage { needle: String },
+
+    /// Image is similar to an illegal image template.
+    Template { template: String, score: f64 },
+
+    /// Text read from an image with OCR contains an illegal needle.
+    ImageText { needle: String, excerpt: String },
+
+    /// Text contains an illegal cryptocurrency address.
+    Address { address: String },
+
+    /// Text contains giveaway wording, along with a cryptocurrency address.
+    GiveawayAddress { needle: String, address: String },
+
+    /// Text is classified as spam by the statistical classifier.
+    Classifier { probability: f64 },
+
+    /// The same text was posted in many chats in a short time.
+    Flood { chats: usize },
+
+    /// Text is a near duplicate of banned spam, with the Hamming distance between signatures.
+    NearDuplicate { distance: u32 },
+
+    /// Multiple verdicts, which together make content illegal.
+    Combined { verdicts: Vec<Verdict> },
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rule::Text { needle } => write!(f, "matched text {:?}", excerpt(needle)),
+            Rule::Phrases { phrases, score } => {
+                let phrases: Vec<String> = phrases.iter().map(|p| excerpt(p)).collect();
+                write!(f, "matched phrases {:?} (score: {:.2})", phrases, score)
+            }
+            Rule::Host { host } => write!(f, "matched host {:?}", host),
+            Rule::HostPart { host, part } => {
+                write!(f, "matched host part {:?} in {:?}", part, host)
+            }
+            Rule::Webpage { needle } => write!(f, "matched webpage text {:?}", excerpt(needle)),
+            Rule::Template { template, score } => write!(
+                f,
+                "matched image template {:?} (score: {:.3})",
+                template, score
+            ),
+            Rule::ImageText { needle, excerpt: text } => write!(
+                f,
+                "matched image text {:?} in {:?}",
+                excerpt(needle),
+                text
+            ),
+            Rule::Address { address } => write!(f, "matched address {:?}", address),
+            Rule::GiveawayAddress { needle, address } => write!(
+                f,
+                "matched giveaway text {:?} with address {:?}",
+                excerpt(needle),
+                address
+            ),
+            Rule::Classifier { probability } => {
+                write!(f, "classified as spam (probability: {:.2})", probability)
+            }
+            Rule::Flood { chats } => write!(f, "same text posted in {} chats", chats),
+            Rule::NearDuplicate { distance } => {
+                write!(f, "near duplicate of banned spam (distance: {})", distance)
+            }
+            Rule::Combined { verdicts } => write!(f, "matched {} rules", verdicts.len()),
+        }
+    }
+}
+
+/// Take a short excerpt of the given text.
+///
+/// Long text is truncated to keep logs and notifications readable.
+pub fn excerpt(text: &str) -> String {
+    if text.chars().count() <= EXCERPT_LEN {
+        return text.into();
+    }
+    let mut excerpt: String = text.chars().take(EXCERPT_LEN).collect();
+    excerpt.push('…');
+    excerpt
+}
+
+/// Take an excerpt of `text` around the first occurrence of `needle`.
+///
+/// Falls back to the start of the text if the needle could not be found.
+pub fn excerpt_around(text: &str, needle: &str) -> String {
+    let start = text
+        .find(needle)
+        .map(|i| text[..i].chars().count().saturating_sub(EXCERPT_LEN / 4))
+        .unwrap_or(0);
+    let tail: String = text.chars().skip(start).collect();
+    excerpt(tail.trim())
+}
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::{
+    future::{self, BoxFuture, Either},
+    prelude::*,
+};
+use tokio::timer::delay_for;
+
+/// Interval to check whether running actions have completed when flushing.
+const FLUSH_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A scheduler for delayed actions, such as self-destructing ban notifications.
+///
+/// Unlike a plain `tokio::spawn`, actions that are still pending can be run immediately with
+/// `flush`, so they aren't lost when the bot shuts down.
+#[derive(Clone, Default)]
+pub struct Scheduler {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// ID for the next scheduled action.
+    next_id: u64,
+
+    /// Actions that are waiting for their delay to pass.
+    pending: HashMap<u64, BoxFuture<'static, ()>>,
+
+    /// Number of actions that are currently running.
+    running: usize,
+}
+
+impl Scheduler {
+    /// Schedule the given action to run after `delay`.
+    pub fn schedule<F>(&self, delay: Duration, action: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let id = {
+            let mut inner = self.inner.lock().unwrap();
+            let id = inner.next_id;
+            inner.next_id += 1;
+            inner.pending.insert(id, action.boxed());
+            id
+        };
+
+        let inner = self.inner.clone();
+        tokio::spawn(async move {
+            delay_for(delay).await;
+
+            // Take the action, it may already have been run by a flush
+            let action = {
+                let mut guard = inner.lock().unwrap();
+                guard.pending.remove(&id).map(|action| {
+                    guard.running += 1;
+                    (action, Running(inner.clone()))
+                })
+            };
+
+            // The running count is decreased when done, even if the action panics
+            if let Some((action, _running)) = action {
+                action.await;
+            }
+        });
+    }
+
+    /// Run all pending actions immediately, and wait for all actions to complete.
+    ///
+    /// Gives up waiting after the given `timeout`, actions that haven't completed are dropped.
+    pub async fn flush(&self, timeout: Duration) {
+        let flush = Box::pin(self.run_all());
+        if let Either::Right(_) = future::select(flush, Box::pin(delay_for(timeout))).await {
+            warn!("Timeout reached while running scheduled actions, dropping the rest");
+        }
+    }
+
+    /// Run all pending actions, and wait for all actions to complete.
+    async fn run_all(&self) {
+        let pending: Vec<_> = self
+            .inner
+            .lock()
+            .unwrap()
+            .pending
+            .drain()
+            .map(|(_, action)| action)
+            .collect();
+        if !pending.is_empty() {
+            info!("Running {} pending scheduled action(s) now...", pending.len());
+            future::join_all(pending).await;
+        }
+
+        // Wait for actions that were already running
+        while self.inner.lock().unwrap().running > 0 {
+            delay_for(FLUSH_POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// Guard marking a scheduled action as running, until dropped.
+struct Running(Arc<Mutex<Inner>>);
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        // Don't panic again on a poisoned lock while unwinding
+        if let Ok(mut inner) = self.0.lock() {
+            inner.running -= 1;
+        }
+    }
+}
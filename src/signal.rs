@@ -0,0 +1,52 @@
+use futures::{
+    future::{self, BoxFuture},
+    prelude::*,
+    stream::{self, BoxStream},
+};
+#[cfg(unix)]
+use tokio_net::signal::unix::{signal, SignalKind};
+
+/// Wait for a shutdown signal.
+///
+/// This completes when the process receives `SIGINT` (Ctrl+C) or `SIGTERM`.
+pub async fn shutdown() {
+    let interrupt: BoxFuture<()> = match tokio_net::signal::ctrl_c() {
+        Ok(stream) => stream.into_future().map(|_| ()).boxed(),
+        Err(err) => {
+            warn!("Failed to listen for interrupt signal: {}", err);
+            future::pending().boxed()
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate: BoxFuture<()> = match signal(SignalKind::terminate()) {
+        Ok(stream) => stream.into_future().map(|_| ()).boxed(),
+        Err(err) => {
+            warn!("Failed to listen for terminate signal: {}", err);
+            future::pending().boxed()
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate: BoxFuture<()> = future::pending().boxed();
+
+    future::select(interrupt, terminate).await;
+    info!("Received shutdown signal, shutting down...");
+}
+
+/// Stream of configuration reload signals.
+///
+/// This yields each time the process receives `SIGHUP`. On other platforms this never yields.
+pub fn reload() -> BoxStream<'static, ()> {
+    #[cfg(unix)]
+    let hangup: BoxStream<()> = match signal(SignalKind::hangup()) {
+        Ok(stream) => stream.boxed(),
+        Err(err) => {
+            warn!("Failed to listen for hangup signal: {}", err);
+            stream::pending().boxed()
+        }
+    };
+    #[cfg(not(unix))]
+    let hangup: BoxStream<()> = stream::pending().boxed();
+
+    hangup
+}
@@ -1,12 +1,22 @@
-use std::env;
-use std::sync::Arc;
-
-use crate::config::{self, Config};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
 
+use ban_binance_bot::{
+    config::{self, Config},
+    feed::Feed,
+    scanner::Context,
+};
 use telegram_bot::{
     Api,
     Error as TelegramError,
-    types::{GetMe, User},
+    types::{ChatId, GetMe, User},
+};
+
+use crate::{
+    archive::{self, SpamArchive},
+    chats::{self, ChatStore},
+    fingerprints::FingerprintStore,
+    scheduler::Scheduler,
 };
 
 /// The global application state.
@@ -15,14 +25,29 @@ pub struct State {
     /// The Telegram API bot token.
     token: String,
 
-    /// Configuration.
-    config: Arc<Config>,
+    /// Configuration, may be replaced at runtime when reloaded or when rule feeds are updated.
+    config: Arc<RwLock<Configuration>>,
+
+    /// Held while building a configuration update, so concurrent updates don't undo each other.
+    updating: Arc<Mutex<()>>,
 
     /// The Telegram API client beign used.
     telegram_client: Api,
 
     /// The bot user.
     user: User,
+
+    /// Scheduler for delayed actions.
+    scheduler: Scheduler,
+
+    /// Per-chat settings.
+    chats: ChatStore,
+
+    /// Fingerprints of illegal content, shared between chats.
+    fingerprints: FingerprintStore,
+
+    /// Signatures of banned message text.
+    archive: SpamArchive,
 }
 
 impl State {
@@ -32,18 +57,41 @@ impl State {
     /// Internally this creates the Telegram API client and sets up a connection,
     /// connects to the bot database and more.
     ///
-    /// A handle to the Tokio core reactor must be given to `reactor`.
-    pub async fn init() -> Result<State, Error> {
+    /// The configuration must already be resolved, including any environment overrides.
+    pub async fn init(config: Config) -> Result<State, Error> {
         // Retrieve the Telegram bot token
-        let token = env::var("TELEGRAM_BOT_TOKEN").expect("env var TELEGRAM_BOT_TOKEN not set");
-
-        // Load configuration
-        info!("Loading configuration...");
-        let config = Arc::new(Config::from_path("./config.toml")?);
+        let token = config.telegram.token.clone().ok_or(Error::NoToken)?;
+        let config = Arc::new(config);
 
         // Build the Telegram API
         let telegram_client = Self::create_telegram_client(&token);
 
+        Self::new(token, config, telegram_client).await
+    }
+
+    /// Construct the state with the given configuration and Telegram API client.
+    ///
+    /// This requests the bot user details through the given client.
+    /// Use `Api::with_connector` to run against anything other than the real Telegram Bot API.
+    pub async fn new(token: String, config: Arc<Config>, telegram_client: Api) -> Result<State, Error> {
+        // Load per-chat settings
+        let chats = match &config.general.chat_settings {
+            Some(path) => ChatStore::load(path).map_err(Error::Chats)?,
+            None => {
+                warn!("No chat settings file configured, per-chat settings are not persisted");
+                ChatStore::default()
+            }
+        };
+
+        // Load the spam archive
+        let archive = match &config.archive.path {
+            Some(path) => SpamArchive::load(path).map_err(Error::Archive)?,
+            None => {
+                warn!("No spam archive file configured, banned spam signatures are not persisted");
+                SpamArchive::default()
+            }
+        };
+
         // Request bot user details
         let user = match telegram_client.send(GetMe).await {
             Ok(user) => {
@@ -59,12 +107,110 @@ impl State {
             },
         };
 
-        Ok(State { token, config, telegram_client, user })
+        Ok(State {
+            token,
+            config: Arc::new(RwLock::new(Configuration::new(config))),
+            updating: Arc::new(Mutex::new(())),
+            telegram_client,
+            user,
+            scheduler: Scheduler::default(),
+            chats,
+            fingerprints: FingerprintStore::default(),
+            archive,
+        })
+    }
+
+    /// Get the current configuration.
+    ///
+    /// This returns a snapshot, a configuration reload does not affect it. Keep it for the
+    /// duration of a single audit, so all scanners use the same configuration.
+    pub fn config(&self) -> Arc<Config> {
+        self.config.read().unwrap().merged.clone()
+    }
+
+    /// Get the configuration for the given chat, with its per-chat settings applied.
+    pub fn chat_config(&self, chat: ChatId) -> Arc<Config> {
+        let config = self.config();
+        let settings = self.chats.get(chat);
+        if settings.is_empty() {
+            config
+        } else {
+            Arc::new(settings.apply(&config))
+        }
+    }
+
+    /// Get the per-chat settings store.
+    pub fn chats(&self) -> &ChatStore {
+        &self.chats
     }
 
-    /// Get the configuration.
-    pub fn config(&self) -> &Config {
-        &self.config
+    /// Get the store of fingerprints of illegal content.
+    pub fn fingerprints(&self) -> &FingerprintStore {
+        &self.fingerprints
+    }
+
+    /// Get the scanner context, with HTTP clients shared by all requests.
+    ///
+    /// Like `config`, this returns a snapshot. The context is rebuilt when a reload changes its
+    /// configuration.
+    pub fn context(&self) -> Context {
+        self.config.read().unwrap().context.clone()
+    }
+
+    /// Get the archive of banned message text signatures.
+    pub fn archive(&self) -> &SpamArchive {
+        &self.archive
+    }
+
+    /// Replace the configuration.
+    ///
+    /// The given configuration must be validated first. Rule feeds are merged into it.
+    pub fn set_config(&self, config: Config) {
+        let _updating = self.updating.lock().unwrap();
+        let (old, feeds, mut context) = {
+            let configuration = self.config.read().unwrap();
+            (
+                configuration.base.clone(),
+                configuration.feeds.clone(),
+                configuration.context.clone(),
+            )
+        };
+
+        // Rebuild the context and merge without blocking audits, which read the configuration
+        context.reconfigure(&old, &config);
+        let merged = merge(&config, &feeds);
+
+        let mut configuration = self.config.write().unwrap();
+        configuration.base = Arc::new(config);
+        configuration.merged = Arc::new(merged);
+        configuration.context = context;
+    }
+
+    /// Check whether the rule feed from the given source is known.
+    pub fn has_feed(&self, source: &str) -> bool {
+        self.config.read().unwrap().feeds.contains_key(source)
+    }
+
+    /// Replace the rule feed from the given source.
+    ///
+    /// The configuration with the new feed merged into it is validated first. If it's invalid,
+    /// the current feed is kept.
+    pub fn set_feed(&self, source: &str, feed: Feed) -> Result<(), config::Error> {
+        let _updating = self.updating.lock().unwrap();
+        let (base, mut feeds) = {
+            let configuration = self.config.read().unwrap();
+            (configuration.base.clone(), configuration.feeds.clone())
+        };
+        feeds.insert(source.into(), feed);
+
+        // Merge and validate without blocking audits, which read the configuration
+        let merged = merge(&base, &feeds);
+        merged.validate()?;
+
+        let mut configuration = self.config.write().unwrap();
+        configuration.feeds = feeds;
+        configuration.merged = Arc::new(merged);
+        Ok(())
     }
 
     /// Create a Telegram API client instance, and initiate a connection.
@@ -83,19 +229,75 @@ impl State {
         &self.token
     }
 
+    /// Get the scheduler for delayed actions.
+    pub fn scheduler(&self) -> &Scheduler {
+        &self.scheduler
+    }
+
+    /// Get the username of this bot.
+    pub fn username(&self) -> Option<&str> {
+        self.user.username.as_ref().map(|u| u.as_str())
+    }
+
     /// Check whether the given user is this bot.
     pub fn is_bot_user(&self, user: &User) -> bool {
         self.user.id == user.id && user.is_bot
     }
 }
 
+/// The configuration file, with rule feeds merged into it.
+struct Configuration {
+    /// The loaded configuration file.
+    base: Arc<Config>,
+
+    /// The latest rule feeds, by source.
+    feeds: HashMap<String, Feed>,
+
+    /// The configuration file with the rule feeds merged into it.
+    merged: Arc<Config>,
+
+    /// Long-lived scanner resources, such as pooled HTTP clients, built from the configuration.
+    context: Context,
+}
+
+impl Configuration {
+    fn new(base: Arc<Config>) -> Self {
+        Self {
+            context: Context::new(&base),
+            merged: base.clone(),
+            base,
+            feeds: HashMap::new(),
+        }
+    }
+}
+
+/// Merge the given rule feeds into the configuration file.
+///
+/// Only feeds that are listed in the configuration file are merged, in the listed order.
+fn merge(base: &Config, feeds: &HashMap<String, Feed>) -> Config {
+    let mut config = base.clone();
+    for source in &base.feeds.sources {
+        if let Some(feed) = feeds.get(source) {
+            feed.merge_into(&mut config.scanner);
+        }
+    }
+    config.scanner.compile();
+    config
+}
+
 #[derive(Debug)]
 pub enum Error {
     /// Telegram API error.
     Telegram(TelegramError),
 
-    /// Configuration error.
-    Config(config::Error),
+    /// No Telegram bot token configured.
+    NoToken,
+
+    /// Failed to load per-chat settings.
+    Chats(chats::Error),
+
+    /// Failed to load the spam archive.
+    Archive(archive::Error),
 }
 
 impl From<TelegramError> for Error {
@@ -103,9 +305,3 @@ impl From<TelegramError> for Error {
         Error::Telegram(err)
     }
 }
-
-impl From<config::Error> for Error {
-    fn from(err: config::Error) -> Self {
-        Error::Config(err)
-    }
-}
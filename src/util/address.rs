@@ -0,0 +1,246 @@
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use tiny_keccak::keccak256;
+
+/// The Base58 alphabet, as used by Bitcoin.
+const BASE58: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// The Bech32 data alphabet.
+const BECH32: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// Bech32 checksum generator coefficients.
+const BECH32_GENERATOR: [u32; 5] = [0x3b6a_57b2, 0x2650_8e6d, 0x1ea1_19fa, 0x3d42_33dd, 0x2a14_62b3];
+
+/// Bech32 and Bech32m checksum constants.
+const BECH32_CONSTANTS: [u32; 2] = [1, 0x2bc8_30a3];
+
+lazy_static! {
+    // A regex for words that may be cryptocurrency addresses.
+    static ref CANDIDATE_REGEX: Regex =
+        Regex::new(r"\b[0-9A-Za-z]{25,90}\b").expect("failed to compile address regex");
+}
+
+/// A cryptocurrency address with a valid checksum.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Address {
+    /// The address format.
+    pub format: Format,
+
+    /// The address as written.
+    pub address: String,
+}
+
+/// A cryptocurrency address format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Base58Check, such as legacy Bitcoin addresses.
+    Base58Check,
+
+    /// Bech32 or Bech32m, such as SegWit Bitcoin and BNB Beacon Chain addresses.
+    Bech32,
+
+    /// Hexadecimal with EIP-55 checksum casing, such as Ethereum and BNB Smart Chain addresses.
+    Hex,
+}
+
+impl Address {
+    /// Parse the given address, and validate its checksum.
+    ///
+    /// Hexadecimal addresses in a single case carry no checksum, and are accepted as is.
+    /// Returns `None` if this is not an address, or if the checksum is invalid.
+    pub fn parse(address: &str) -> Option<Self> {
+        let format = if is_hex(address) {
+            Format::Hex
+        } else if is_bech32(address) {
+            Format::Bech32
+        } else if is_base58check(address) {
+            Format::Base58Check
+        } else {
+            return None;
+        };
+
+        Some(Self {
+            format,
+            address: address.into(),
+        })
+    }
+
+    /// Get the normalized address, used to compare addresses.
+    ///
+    /// Hexadecimal and Bech32 addresses are case insensitive, and are lowercased.
+    pub fn normalized(&self) -> String {
+        match self.format {
+            Format::Base58Check => self.address.clone(),
+            Format::Hex | Format::Bech32 => self.address.to_lowercase(),
+        }
+    }
+}
+
+/// Find all cryptocurrency addresses with a valid checksum in the given text.
+///
+/// Addresses are returned in order, duplicates are not removed.
+pub fn find_addresses(text: &str) -> Vec<Address> {
+    CANDIDATE_REGEX
+        .find_iter(text)
+        .filter_map(|candidate| Address::parse(candidate.as_str()))
+        .collect()
+}
+
+/// Check whether the given text is a hexadecimal address with a valid EIP-55 checksum.
+fn is_hex(address: &str) -> bool {
+    if address.len() != 42 || !address.starts_with("0x") {
+        return false;
+    }
+    let digits = &address[2..];
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return false;
+    }
+
+    // Addresses in a single case have no checksum
+    let lowercase = digits.to_lowercase();
+    if digits == lowercase || digits == digits.to_uppercase() {
+        return true;
+    }
+
+    // Letters are uppercase if the matching nibble of the hash of the address is 8 or higher
+    let hash = keccak256(lowercase.as_bytes());
+    digits.chars().enumerate().all(|(i, c)| {
+        let nibble = if i % 2 == 0 {
+            hash[i / 2] >> 4
+        } else {
+            hash[i / 2] & 0x0f
+        };
+        !c.is_ascii_alphabetic() || c.is_ascii_uppercase() == (nibble >= 8)
+    })
+}
+
+/// Check whether the given text is a Bech32 or Bech32m string with a valid checksum.
+fn is_bech32(address: &str) -> bool {
+    // Must not be mixed case
+    let lowercase = address.to_lowercase();
+    if address != lowercase && address != address.to_uppercase() {
+        return false;
+    }
+
+    // Split human readable part and data, map data to values
+    let separator = match lowercase.rfind('1') {
+        Some(separator) if separator > 0 && lowercase.len() - separator > 6 => separator,
+        _ => return false,
+    };
+    let (hrp, data) = (&lowercase[..separator], &lowercase[separator + 1..]);
+    let values: Option<Vec<u8>> = data
+        .chars()
+        .map(|c| BECH32.find(c).map(|value| value as u8))
+        .collect();
+    let values = match values {
+        Some(values) => values,
+        None => return false,
+    };
+
+    // Expand the human readable part, and verify the checksum
+    let expanded = hrp
+        .bytes()
+        .map(|c| c >> 5)
+        .chain(Some(0))
+        .chain(hrp.bytes().map(|c| c & 0x1f))
+        .chain(values);
+    BECH32_CONSTANTS.contains(&bech32_polymod(expanded))
+}
+
+/// Compute the Bech32 checksum polynomial over the given values.
+fn bech32_polymod<I: IntoIterator<Item = u8>>(values: I) -> u32 {
+    let mut checksum: u32 = 1;
+    for value in values {
+        let top = checksum >> 25;
+        checksum = ((checksum & 0x01ff_ffff) << 5) ^ u32::from(value);
+        for (i, generator) in BECH32_GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
+
+/// Check whether the given text is a Base58Check encoded address with a valid checksum.
+///
+/// Only 25 byte payloads are accepted, being a version byte, a 20 byte hash and a checksum.
+fn is_base58check(address: &str) -> bool {
+    let bytes = match base58_decode(address) {
+        Some(bytes) if bytes.len() == 25 => bytes,
+        _ => return false,
+    };
+
+    let (payload, checksum) = bytes.split_at(21);
+    Sha256::digest(&Sha256::digest(payload))[..4] == *checksum
+}
+
+/// Decode the given Base58 text into bytes.
+fn base58_decode(text: &str) -> Option<Vec<u8>> {
+    let mut bytes: Vec<u8> = vec![];
+    for c in text.chars() {
+        let mut carry = BASE58.find(c)? as u32;
+        for byte in bytes.iter_mut().rev() {
+            carry += u32::from(*byte) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.insert(0, carry as u8);
+            carry >>= 8;
+        }
+    }
+
+    // Leading ones encode leading zero bytes
+    let zeros = text.chars().take_while(|&c| c == '1').count();
+    bytes.splice(0..0, vec![0; zeros]);
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let format = |address: &str| Address::parse(address).map(|address| address.format);
+
+        // Base58Check
+        assert_eq!(format("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa"), Some(Format::Base58Check));
+        assert_eq!(format("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNb"), None);
+
+        // Bech32 and Bech32m
+        assert_eq!(format("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"), Some(Format::Bech32));
+        assert_eq!(format("BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4"), Some(Format::Bech32));
+        assert_eq!(
+            format("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0"),
+            Some(Format::Bech32),
+        );
+        assert_eq!(format("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5"), None);
+        assert_eq!(format("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kV8f3t4"), None);
+
+        // EIP-55
+        assert_eq!(format("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"), Some(Format::Hex));
+        assert_eq!(format("0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed"), Some(Format::Hex));
+        assert_eq!(format("0x5aAeb6053f3E94C9b9A09f33669435E7Ef1BeAed"), None);
+        assert_eq!(format("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAe"), None);
+    }
+
+    #[test]
+    fn test_find_addresses() {
+        let text = "Send 0.1 BTC to 1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa, or ETH to \
+            0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed! Binance US celebrates 5000 participants";
+        let addresses: Vec<String> = find_addresses(text)
+            .into_iter()
+            .map(|address| address.normalized())
+            .collect();
+        assert_eq!(
+            addresses,
+            [
+                "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa",
+                "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed",
+            ],
+        );
+        assert!(find_addresses("Celebrating Our New Crypto Exchange").is_empty());
+    }
+}
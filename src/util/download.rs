@@ -1,27 +1,19 @@
 use std::fs::File;
 use std::io::Write;
-use std::time::Duration;
 
 use futures::prelude::*;
-use reqwest::Client;
 use tempfile::{Builder, TempPath};
 use url::Url;
 
+use super::http::Clients;
+
 /// Download a file at the given URL to a temporary file on the system.
 /// The downloaded file and path is returned.
 ///
 /// The actual downloaded file is automatically deleted from disk when the last file handle
 /// (`File`) is dropped. See `tempfile::NamedTempFile` for more details.
 // TODO: make this properly async, the download process isn't at this moment
-pub async fn download_temp(url: &Url) -> Result<(File, TempPath), Error> {
-    // Build the download client
-    // TODO: use a global client instance
-    let client = Client::builder()
-        .timeout(Duration::from_secs(60))
-        .connect_timeout(Duration::from_secs(60))
-        .build()
-        .expect("failed to build file downloading client");
-
+pub async fn download_temp(http: &Clients, url: &Url) -> Result<(File, TempPath), Error> {
     // Get file name to suffix temporary downloaded file with
     let name = url.path_segments().and_then(|s| s.last()).unwrap_or("");
 
@@ -37,7 +29,7 @@ pub async fn download_temp(url: &Url) -> Result<(File, TempPath), Error> {
     // TODO: check status code
 
     // Make the request, obtain the repsonse
-    let mut response = client.get(url.as_str()).send().map_err(Error::Request).await?;
+    let mut response = http.download().get(url.as_str()).send().map_err(Error::Request).await?;
 
     // Write response body chunks to file
     while let Some(chunk) = response.chunk().map_err(Error::Request).await? {
@@ -1,42 +1,39 @@
 use futures::{future::Future, prelude::*};
 
-/// Same as `select_ok`, but for booleans.
+/// Same as `select_ok`, but for options.
 ///
-/// This returns with `true` as soon as a future in `iter` yields `true`.
-/// If all yield `false`, `false` is returned instead.
+/// This returns with the value as soon as a future in `iter` yields `Some`.
+/// If all yield `None`, `None` is returned instead.
 ///
 /// See: `futures::future::select_ok`
-pub async fn select_true<I>(iter: I) -> bool
+pub async fn select_some<I, T>(iter: I) -> Option<T>
     where I: IntoIterator,
-          I::Item: Future<Output = bool> + Send,
+          I::Item: Future<Output = Option<T>> + Send,
+          T: Send,
 {
     // Collect the list, ensure there's at least one future to complete
     // TODO: do not create list here
     let list: Vec<_> = iter.into_iter().collect();
     if list.is_empty() {
-        return false;
+        return None;
     }
 
     futures::future::select_ok(
-        list.into_iter().map(|f| btr(f).boxed()),
-    ).await.is_ok()
+        list.into_iter().map(|f| otr(f).boxed()),
+    ).await.ok().map(|(value, _)| value)
 }
 
-/// Convert a `bool` future to an empty `Result` future.
+/// Convert an `Option` future to a `Result` future.
 ///
-/// Function name stands for: Boolean To Result
+/// Function name stands for: Option To Result
 ///
-/// This project commonly uses booleans as future return type.
+/// This project commonly uses options as future return type.
 /// Some functions, such as `select_ok` require the future to return a `Result` instead.
 ///
 /// Converts:
-/// - `true` to `Ok(())`
-/// - `false` to `Err(())`
-pub async fn btr<F>(future: F) -> Result<(), ()>
-where F: Future<Output = bool> {
-    if future.await {
-        Ok(())
-    } else {
-        Err(())
-    }
+/// - `Some(value)` to `Ok(value)`
+/// - `None` to `Err(())`
+pub async fn otr<F, T>(future: F) -> Result<T, ()>
+where F: Future<Output = Option<T>> {
+    future.await.ok_or(())
 }
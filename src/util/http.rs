@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::{
+    channel::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    lock::Mutex as AsyncMutex,
+    prelude::*,
+};
+use reqwest::{header, Client, RedirectPolicy};
+use url::Url;
+
+use crate::config::Http;
+
+/// Long-lived HTTP clients, shared by everything that makes HTTP requests.
+///
+/// Clients pool their connections, so requests to the same host reuse connections instead of
+/// doing a new DNS lookup and TLS handshake each time. This is cheap to clone, clones share the
+/// same clients and limits.
+#[derive(Clone)]
+pub struct Clients {
+    /// Client to audit URLs with, following redirects and accepting invalid certificates.
+    audit: Client,
+
+    /// Client to download files and rule feeds with.
+    download: Client,
+
+    /// Maximum number of concurrent audit requests to a single host, `0` for no limit.
+    max_per_host: usize,
+
+    /// Request slots for each host with requests in progress.
+    hosts: Hosts,
+}
+
+/// Request slots by host.
+type Hosts = Arc<Mutex<HashMap<String, Slots>>>;
+
+impl Clients {
+    /// Build the clients with the given configuration.
+    pub fn new(config: &Http) -> Self {
+        let audit = Client::builder()
+            .danger_accept_invalid_certs(true)
+            .redirect(RedirectPolicy::limited(25))
+            .timeout(Duration::from_secs(config.timeout))
+            .connect_timeout(Duration::from_secs(config.connect_timeout))
+            .default_headers(user_agent(&config.audit_user_agent))
+            .build()
+            .expect("failed to build URL auditing client");
+        let download = Client::builder()
+            .timeout(Duration::from_secs(config.download_timeout))
+            .connect_timeout(Duration::from_secs(config.connect_timeout))
+            .default_headers(user_agent(&config.user_agent))
+            .build()
+            .expect("failed to build downloading client");
+
+        Self {
+            audit,
+            download,
+            max_per_host: config.max_per_host,
+            hosts: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Get the client to audit URLs and webpages with.
+    ///
+    /// This follows redirects, and accepts invalid certificates as spam sites often have them.
+    pub fn audit(&self) -> &Client {
+        &self.audit
+    }
+
+    /// Get the client to download files and rule feeds with.
+    pub fn download(&self) -> &Client {
+        &self.download
+    }
+
+    /// Wait for a free request slot for the host of the given URL.
+    ///
+    /// Keep the returned permit until the request is done, the slot is freed when it's dropped.
+    /// Only audit requests are limited, downloads such as Telegram files don't take a slot.
+    pub async fn permit(&self, url: &Url) -> Permit {
+        if self.max_per_host == 0 {
+            return Permit {
+                slots: None,
+                hosts: self.hosts.clone(),
+            };
+        }
+
+        let host = url.host_str().unwrap_or("").to_lowercase();
+        let slots = self
+            .hosts
+            .lock()
+            .unwrap()
+            .entry(host.clone())
+            .or_insert_with(|| Slots::new(self.max_per_host))
+            .clone();
+        slots.acquire().await;
+        Permit {
+            slots: Some((host, slots)),
+            hosts: self.hosts.clone(),
+        }
+    }
+}
+
+/// Build the default headers for a client, sending the given user agent.
+fn user_agent(user_agent: &str) -> header::HeaderMap {
+    let mut headers = header::HeaderMap::new();
+    if let Ok(user_agent) = header::HeaderValue::from_str(user_agent) {
+        headers.insert(header::USER_AGENT, user_agent);
+    }
+    headers
+}
+
+/// A request slot for a host, freed when dropped.
+pub struct Permit {
+    /// The host and its slots, `None` if requests aren't limited.
+    slots: Option<(String, Slots)>,
+
+    /// Request slots by host, to forget the host when it has no requests left.
+    hosts: Hosts,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some((host, slots)) = self.slots.take() {
+            // Slots are only shared while holding the lock, so this can't race with a new request
+            let hosts = self.hosts.lock();
+            slots.release();
+            if let Ok(mut hosts) = hosts {
+                if slots.is_idle() {
+                    hosts.remove(&host);
+                }
+            }
+        }
+    }
+}
+
+/// A fixed number of request slots, handed out in order.
+///
+/// Free slots are tokens in a channel, taking a token takes a slot.
+#[derive(Clone)]
+struct Slots {
+    free: UnboundedSender<()>,
+    taken: Arc<AsyncMutex<UnboundedReceiver<()>>>,
+}
+
+impl Slots {
+    fn new(count: usize) -> Self {
+        let (free, taken) = mpsc::unbounded();
+        for _ in 0..count {
+            let _ = free.unbounded_send(());
+        }
+        Self {
+            free,
+            taken: Arc::new(AsyncMutex::new(taken)),
+        }
+    }
+
+    /// Wait for a free slot, and take it.
+    async fn acquire(&self) {
+        // The sender is kept in self, so the channel is never closed
+        let _ = self.taken.lock().await.next().await;
+    }
+
+    /// Free a taken slot.
+    fn release(&self) {
+        let _ = self.free.unbounded_send(());
+    }
+
+    /// Check whether no one else holds or waits for a slot.
+    ///
+    /// Besides this instance, the slots are only shared with the map of hosts.
+    fn is_idle(&self) -> bool {
+        Arc::strong_count(&self.taken) <= 2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    #[test]
+    fn test_permit() {
+        let clients = Clients::new(&Http {
+            max_per_host: 1,
+            ..Http::default()
+        });
+        let url = Url::parse("https://example.com/a").unwrap();
+        let other = Url::parse("https://example.org/a").unwrap();
+
+        block_on(async {
+            let permit = clients.permit(&url).await;
+
+            // Other hosts have their own slots, the same host has to wait
+            assert!(clients.permit(&other).now_or_never().is_some());
+            let mut waiting = clients.permit(&url).boxed();
+            assert!((&mut waiting).now_or_never().is_none());
+
+            drop(permit);
+            assert!(waiting.now_or_never().is_some());
+        });
+
+        // Hosts without requests in progress are forgotten
+        assert!(clients.hosts.lock().unwrap().is_empty());
+    }
+}
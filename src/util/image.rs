@@ -1,12 +1,10 @@
-#[cfg(feature = "ocr")]
+use std::path::PathBuf;
 use std::sync::Arc;
 
 use dssim::{ToRGBAPLU, RGBAPLU};
-use image::{GenericImageView, Rgba};
+use image::{imageops, FilterType, GenericImageView, Rgba};
 use imgref::ImgVec;
 use rgb::RGBA;
-#[cfg(feature = "ocr")]
-use tempfile::TempPath;
 
 /// Convert the given generic image to an `ImgVec` used with DSSIM for image comparing.
 pub fn to_imgvec(input: &impl GenericImageView<Pixel = Rgba<u8>>) -> ImgVec<RGBAPLU> {
@@ -18,9 +16,38 @@ pub fn to_imgvec(input: &impl GenericImageView<Pixel = Rgba<u8>>) -> ImgVec<RGBA
     ImgVec::new(pixels, input.width() as usize, input.height() as usize)
 }
 
+/// Compute the difference hash (dHash) of the image at the given path.
+///
+/// This is a perceptual hash: similar images, such as resized or recompressed copies, have hashes
+/// with a small Hamming distance. See `hash_distance`.
+pub async fn dhash(path: Arc<PathBuf>) -> Result<u64, ()> {
+    tokio_executor::blocking::run(move || {
+        let image = image::open(path.as_path()).map_err(|err| {
+            warn!("Failed to open image to hash: {}", err);
+            ()
+        })?;
+
+        // Shrink to 9x8 grayscale, compare each pixel to its right neighbour
+        let image = imageops::resize(&image.grayscale(), 9, 8, FilterType::Triangle);
+        let mut hash = 0u64;
+        for y in 0..8 {
+            for x in 0..8 {
+                let brighter = image.get_pixel(x, y)[0] > image.get_pixel(x + 1, y)[0];
+                hash = (hash << 1) | u64::from(brighter);
+            }
+        }
+        Ok(hash)
+    }).await
+}
+
+/// Get the Hamming distance between two perceptual hashes.
+pub fn hash_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
 /// Read text from image at given path.
 #[cfg(feature = "ocr")]
-pub async fn read_text(path: Arc<TempPath>) -> Result<String, ()> {
+pub async fn read_text(path: Arc<PathBuf>) -> Result<String, ()> {
     // Run OCR to get text from image in threadpool
     tokio_executor::blocking::run(move || {
         // Get the path as a string
@@ -1,7 +1,10 @@
+pub mod address;
 pub mod download;
 pub mod future;
+pub mod http;
 pub mod image;
 pub mod telegram;
+pub mod text;
 pub mod url;
 #[cfg(feature = "ffmpeg")]
 pub mod video;
@@ -0,0 +1,310 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use sha2::{Digest, Sha256};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+/// Get the skeleton of the given text, used to match text regardless of obfuscation.
+///
+/// Spam replaces characters with lookalikes from other scripts, and inserts invisible characters
+/// to bypass text matching. The skeleton undoes this:
+///
+/// - compatibility decomposition (NFKD), turning fullwidth and styled letters into plain ones
+/// - strip diacritics, zero-width and bidirectional control characters
+/// - replace lookalikes from other scripts with the ASCII character they resemble
+/// - lowercase
+/// - trim, and collapse whitespace into a single space
+///
+/// Two texts that look alike have the same skeleton.
+pub fn skeleton(text: &str) -> String {
+    let mut skeleton = String::with_capacity(text.len());
+    let mut space = false;
+
+    for c in text.nfkd() {
+        if is_combining_mark(c) || is_invisible(c) {
+            continue;
+        }
+        if c.is_whitespace() {
+            space = !skeleton.is_empty();
+            continue;
+        }
+
+        if space {
+            skeleton.push(' ');
+            space = false;
+        }
+        skeleton.extend(lookalike(c).to_lowercase());
+    }
+
+    skeleton
+}
+
+/// Get a fingerprint of the given text, being a hash of its skeleton.
+///
+/// Texts that look alike have the same fingerprint. Fingerprints are only stable within a single
+/// run of the bot.
+pub fn fingerprint(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    skeleton(text).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Get the words of the given text, being the alphanumeric parts of its skeleton.
+pub fn words(text: &str) -> Vec<String> {
+    skeleton(text)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.into())
+        .collect()
+}
+
+/// Get the SimHash signature of the given words.
+///
+/// Near duplicate texts, such as spam with a few words changed, have signatures with a small
+/// Hamming distance. Signatures are stable, and may be persisted.
+pub fn simhash<S: AsRef<str>>(words: &[S]) -> u64 {
+    let mut weights = [0i64; 64];
+    for word in words {
+        let digest = Sha256::digest(word.as_ref().as_bytes());
+        let hash = digest[..8]
+            .iter()
+            .fold(0u64, |hash, byte| (hash << 8) | u64::from(*byte));
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            if (hash >> bit) & 1 == 1 {
+                *weight += 1;
+            } else {
+                *weight -= 1;
+            }
+        }
+    }
+
+    weights
+        .iter()
+        .enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .fold(0, |signature, (bit, _)| signature | 1 << bit)
+}
+
+/// Check whether the given character is invisible, such as zero-width and bidirectional control
+/// characters.
+fn is_invisible(c: char) -> bool {
+    match c {
+        '\u{00AD}' | '\u{034F}' | '\u{061C}' | '\u{115F}' | '\u{1160}' | '\u{17B4}'
+        | '\u{17B5}' | '\u{180E}' | '\u{200B}'..='\u{200F}' | '\u{202A}'..='\u{202E}'
+        | '\u{2060}'..='\u{206F}' | '\u{3164}' | '\u{FE00}'..='\u{FE0F}' | '\u{FEFF}'
+        | '\u{FFA0}' => true,
+        _ => false,
+    }
+}
+
+/// Get the ASCII character the given character resembles.
+///
+/// This covers lookalikes in the Cyrillic, Greek, Armenian, Cherokee, Lisu, Tifinagh, Gothic,
+/// Old Italic and Deseret scripts, and some Latin extensions. Other characters are returned as is.
+fn lookalike(c: char) -> char {
+    match c {
+        // Cyrillic
+        'А' => 'A',
+        'В' => 'B',
+        'С' => 'C',
+        'Е' => 'E',
+        'Н' => 'H',
+        'І' | 'Ӏ' => 'I',
+        'Ј' => 'J',
+        'К' => 'K',
+        'М' => 'M',
+        'О' => 'O',
+        'Р' => 'P',
+        'Ԛ' => 'Q',
+        'Ѕ' => 'S',
+        'Т' => 'T',
+        'Ԝ' => 'W',
+        'Х' => 'X',
+        'У' | 'Ү' => 'Y',
+        'а' => 'a',
+        'ь' => 'b',
+        'с' => 'c',
+        'ԁ' => 'd',
+        'е' => 'e',
+        'һ' => 'h',
+        'і' => 'i',
+        'ј' => 'j',
+        'ӏ' => 'l',
+        'о' => 'o',
+        'р' => 'p',
+        'ԛ' => 'q',
+        'г' => 'r',
+        'ѕ' => 's',
+        'ԝ' => 'w',
+        'х' => 'x',
+        'у' | 'ү' => 'y',
+
+        // Greek
+        'Α' => 'A',
+        'Β' => 'B',
+        'Ε' => 'E',
+        'Η' => 'H',
+        'Ι' => 'I',
+        'Κ' => 'K',
+        'Μ' => 'M',
+        'Ν' => 'N',
+        'Ο' => 'O',
+        'Ρ' => 'P',
+        'Τ' => 'T',
+        'Χ' => 'X',
+        'Υ' => 'Y',
+        'Ζ' => 'Z',
+        'α' => 'a',
+        'ϲ' => 'c',
+        'ε' => 'e',
+        'ι' => 'i',
+        'ϳ' => 'j',
+        'κ' => 'k',
+        'ο' => 'o',
+        'ρ' => 'p',
+        'τ' => 't',
+        'υ' => 'u',
+        'ν' => 'v',
+        'χ' => 'x',
+        'γ' => 'y',
+
+        // Latin extensions
+        'Ɩ' => 'l',
+        'ƅ' => 'b',
+        'ɑ' => 'a',
+        'ɡ' => 'g',
+        'ı' | 'ɩ' | 'ɪ' => 'i',
+        'ȷ' => 'j',
+        'ʜ' => 'h',
+        'ʀ' => 'r',
+        'ᴠ' => 'v',
+        'ᴡ' => 'w',
+        'ʏ' => 'y',
+
+        // Armenian
+        'ց' => 'g',
+        'հ' => 'h',
+        'ո' => 'n',
+        'օ' => 'o',
+        'ս' => 'u',
+
+        // Cherokee
+        'Ꭺ' => 'A',
+        'Ᏼ' => 'B',
+        'Ꮯ' => 'C',
+        'Ꭼ' => 'E',
+        'Ꮋ' => 'H',
+        'Ꭻ' => 'J',
+        'Ꮶ' => 'K',
+        'Ꮇ' => 'M',
+        'Ꮲ' => 'P',
+        'Ꮪ' => 'S',
+        'Ꭲ' => 'T',
+        'Ꮃ' => 'W',
+        'Ꮓ' => 'Z',
+
+        // Lisu
+        'ꓮ' => 'A',
+        'ꓐ' => 'B',
+        'ꓚ' => 'C',
+        'ꓓ' => 'D',
+        'ꓰ' => 'E',
+        'ꓝ' => 'F',
+        'ꓖ' => 'G',
+        'ꓧ' => 'H',
+        'ꓲ' => 'I',
+        'ꓙ' => 'J',
+        'ꓗ' => 'K',
+        'ꓡ' => 'L',
+        'ꓟ' => 'M',
+        'ꓠ' => 'N',
+        'ꓳ' => 'O',
+        'ꓑ' => 'P',
+        'ꓣ' => 'R',
+        'ꓢ' => 'S',
+        'ꓔ' => 'T',
+        'ꓴ' => 'U',
+        'ꓦ' => 'V',
+        'ꓪ' => 'W',
+        'ꓫ' => 'X',
+        'ꓬ' => 'Y',
+        'ꓜ' => 'Z',
+
+        // Tifinagh
+        'ⴹ' => 'E',
+        'ⵏ' => 'I',
+        'ⵔ' => 'O',
+        'ⴖ' => 'n',
+
+        // Gothic
+        '𐌰' => 'a',
+        '𐌱' => 'b',
+        '𐌴' => 'e',
+        '𐌷' => 'h',
+        '𐌹' => 'i',
+        '𐌾' => 'j',
+        '𐌺' => 'k',
+        '𐌼' => 'm',
+        '𐌽' => 'n',
+        '𐍃' => 's',
+        '𐍄' => 't',
+        '𐌿' => 'u',
+
+        // Old Italic
+        '𐌀' => 'A',
+        '𐌁' => 'B',
+        '𐌉' => 'I',
+        '𐌊' => 'K',
+        '𐌏' => 'O',
+        '𐌕' => 'T',
+        '𐌗' => 'X',
+
+        // Deseret, and lookalikes used along with it
+        '𐐃' => 'O',
+        '𐐒' => 'B',
+        '𐐫' => 'o',
+        '𐑈' => 's',
+        '𐩒' => 'O',
+
+        c => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skeleton() {
+        assert_eq!(skeleton("Binance US"), "binance us");
+        assert_eq!(skeleton("  Βἱnаⴖcе \n\t US "), "binance us");
+        assert_eq!(skeleton("𐌉МΡOR𐌕АΝΤ"), "important");
+        assert_eq!(skeleton("Ｂｉｎａｎｃｅ 𝐔𝐒"), "binance us");
+        assert_eq!(skeleton("thís ís sómé tést"), "this is some test");
+        assert_eq!(skeleton("Bi\u{200B}nan\u{202E}ce\u{FEFF}"), "binance");
+        assert_eq!(skeleton("币安 交易所"), "币安 交易所");
+        assert_eq!(skeleton("\u{200B} \u{200D}"), "");
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        assert_eq!(fingerprint("Binance US"), fingerprint("  Βἱnаⴖcе \n US"));
+        assert_ne!(fingerprint("Binance US"), fingerprint("Binance USA"));
+    }
+
+    #[test]
+    fn test_simhash() {
+        let spam = "Binance is celebrating the launch of its new exchange! The first 5000 \
+            participants receive a deposit bonus, send BTC to the address below to join the event";
+        let tweaked = "Binance is celebrating the launch of its new exchange! The first 3000 \
+            participants get a deposit bonus, send BTC to the address below to join the giveaway";
+        let ham = "Good morning everyone, does anyone know when the next release of the wallet app \
+            is planned? I cannot find it in the changelog";
+        let distance = |a, b| (simhash(&words(a)) ^ simhash(&words(b))).count_ones();
+
+        assert_eq!(simhash(&words(spam)), 0xbb37_3d3d_ff47_0c9f);
+        assert_eq!(distance(spam, tweaked), 4);
+        assert_eq!(distance(spam, ham), 17);
+        assert_eq!(words("Βἱnаⴖcе US, event!"), ["binance", "us", "event"]);
+    }
+}
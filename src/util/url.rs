@@ -1,14 +1,13 @@
 use std::str;
-use std::time::Duration;
 
 use itertools::Itertools;
 use linkify::{LinkFinder, LinkKind};
 use regex::Regex;
-use reqwest::{r#async::Client, Error as ResponseError, RedirectPolicy};
+use reqwest::Error as ResponseError;
 use telegram_bot::types::{MessageEntity, MessageEntityKind};
 use url::Url;
 
-use crate::config::SCAN_WEBPAGE_URL_HOSTS;
+use super::http::Clients;
 
 lazy_static! {
     // A regex for detecting URLs.
@@ -37,7 +36,9 @@ pub fn find_urls(text: &str) -> Vec<Url> {
 }
 
 /// List all sketchy URLs from a page body to scan.
-pub fn find_page_urls(body: &[u8]) -> Vec<Url> {
+///
+/// Only URLs with any of the given `hosts` are listed, such as URL shorteners.
+pub fn find_page_urls(body: &[u8], hosts: &[String]) -> Vec<Url> {
     // Body needs to be UTF-8 for URL scanning
     let body = match str::from_utf8(body) {
         Ok(body) => body,
@@ -57,9 +58,9 @@ pub fn find_page_urls(body: &[u8]) -> Vec<Url> {
         .map(|link| link.as_str())
         .filter_map(parse_url)
         .filter(|url| match url.host_str() {
-            Some(host) => SCAN_WEBPAGE_URL_HOSTS
+            Some(host) => hosts
                 .iter()
-                .any(|scan_host| scan_host == &host),
+                .any(|scan_host| scan_host == host),
             None => false,
         })
         .dedup()
@@ -107,22 +108,13 @@ fn parse_url(url: &str) -> Option<Url> {
 /// Follow redirects on the given URL, and return the final full URL.
 ///
 /// This is used to obtain share URLs from shortened links.
-pub async fn follow_url(url: &Url) -> Result<Url, FollowError> {
-    // Build the URL client
-    // TODO: use a global client instance
-    let client = Client::builder()
-        .danger_accept_invalid_certs(true)
-        .redirect(RedirectPolicy::limited(25))
-        .timeout(Duration::from_secs(15))
-        .connect_timeout(Duration::from_secs(20))
-        .build()
-        .expect("failed to build URL forward resolver client");
-
+pub async fn follow_url(http: &Clients, url: &Url) -> Result<Url, FollowError> {
     debug!("Test URL for redirects: {}", url.as_str());
 
     // Send request to URL, get last known URL
     // TODO: validate status !response.status.is_success()
-    let url = match client.get(url.as_str()).send().await {
+    let _permit = http.permit(url).await;
+    let url = match http.audit().get(url.as_str()).send().await {
         Ok(response) => Ok(response.url().clone()),
         Err(err) => err.url().cloned().ok_or(FollowError::Request(err)),
     };
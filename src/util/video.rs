@@ -1,5 +1,5 @@
+use std::path::Path;
 use std::process::Stdio;
-use std::sync::Arc;
 
 use tempfile::{Builder, TempPath};
 use tokio::net::process::Command;
@@ -10,7 +10,7 @@ use tokio::net::process::Command;
 /// The temporary file the frame is written to is returned.
 ///
 /// This operation is expensive.
-pub async fn extract_frames(path: Arc<TempPath>) -> Result<Arc<TempPath>, ()> {
+pub async fn extract_frames(path: &Path) -> Result<TempPath, ()> {
     let input = path.to_str().expect("failed to get path string");
 
     // Create new temporary file
@@ -53,5 +53,5 @@ pub async fn extract_frames(path: Arc<TempPath>) -> Result<Arc<TempPath>, ()> {
         Ok(_) => {},
     }
 
-    Ok(Arc::new(frame_path))
+    Ok(frame_path)
 }
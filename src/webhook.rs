@@ -0,0 +1,302 @@
+use std::convert::Infallible;
+use std::error;
+use std::fmt;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use ban_binance_bot::config::Webhook;
+use futures::{channel::mpsc, prelude::*};
+use hyper::{
+    server::conn::AddrStream,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request as HttpRequest, Response, Server, StatusCode,
+};
+use telegram_bot::{
+    types::{
+        Error as RawError, HttpRequest as TelegramRequest, JsonRequestType,
+        JsonTrueToUnitResponse, Request, RequestType, RequestUrl, Update,
+    },
+    Error as TelegramError,
+};
+use url::Url;
+
+use crate::state::State;
+
+/// Header Telegram sends the webhook secret token in.
+const SECRET_HEADER: &str = "X-Telegram-Bot-Api-Secret-Token";
+
+/// Number of received updates to buffer, before applying backpressure to Telegram.
+const UPDATE_BUFFER: usize = 64;
+
+/// Maximum size in bytes of a webhook request body.
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// Register the webhook with Telegram, so updates are sent to the configured URL.
+pub async fn register(state: &State, config: &Webhook) -> Result<(), TelegramError> {
+    info!("Registering Telegram webhook...");
+    state
+        .telegram_client()
+        .send(SetWebhook {
+            url: config.url.clone(),
+            secret_token: config.secret.clone(),
+        })
+        .await
+}
+
+/// Remove any registered webhook, so updates can be received through long polling.
+pub async fn remove(state: &State) -> Result<(), TelegramError> {
+    debug!("Removing any registered Telegram webhook...");
+    state.telegram_client().send(DeleteWebhook {}).await
+}
+
+/// Start listening for webhook requests, until the given shutdown future completes.
+///
+/// Returns a stream of received updates, and the server future that must be spawned to accept
+/// requests. On shutdown, new requests are refused so Telegram sends them again later, and the
+/// server stops. The stream then ends after the updates that were already accepted.
+pub fn listen<F>(
+    config: Webhook,
+    shutdown: F,
+) -> Result<
+    (
+        impl Stream<Item = Update>,
+        impl Future<Output = Result<(), hyper::Error>>,
+    ),
+    Error,
+>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    // Only accept updates on the path of the configured URL, carrying the secret
+    let path = Url::parse(&config.url).map_err(Error::Url)?.path().to_owned();
+    let secret = config.secret.clone().ok_or(Error::NoSecret)?;
+    let (sender, receiver) = mpsc::channel(UPDATE_BUFFER);
+    let closing = Arc::new(AtomicBool::new(false));
+
+    let make_service = {
+        let closing = closing.clone();
+        make_service_fn(move |_: &AddrStream| {
+            let path = path.clone();
+            let secret = secret.clone();
+            let sender = sender.clone();
+            let closing = closing.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let sender = if closing.load(Ordering::SeqCst) {
+                        None
+                    } else {
+                        Some(sender.clone())
+                    };
+                    handle_request(request, path.clone(), secret.clone(), sender)
+                }))
+            }
+        })
+    };
+
+    let server = Server::try_bind(&config.listen)
+        .map_err(Error::Bind)?
+        .serve(make_service)
+        .with_graceful_shutdown(shutdown.map(move |_| {
+            info!("Stopping Telegram webhook listener...");
+            closing.store(true, Ordering::SeqCst);
+        }));
+    info!("Listening for Telegram webhook requests on {}", config.listen);
+
+    Ok((receiver, server))
+}
+
+/// Handle a single webhook request.
+///
+/// Requests must be `POST` requests to the webhook path, and must carry the secret token.
+/// Received updates are sent to `sender`. If there's no sender because the bot is shutting down,
+/// requests are refused so Telegram sends the update again later.
+async fn handle_request(
+    request: HttpRequest<Body>,
+    path: String,
+    secret: String,
+    sender: Option<mpsc::Sender<Update>>,
+) -> Result<Response<Body>, Infallible> {
+    // Validate the request path, method and secret
+    if request.uri().path() != path {
+        return Ok(status(StatusCode::NOT_FOUND));
+    }
+    if request.method() != Method::POST {
+        return Ok(status(StatusCode::METHOD_NOT_ALLOWED));
+    }
+    let token = request
+        .headers()
+        .get(SECRET_HEADER)
+        .map(|token| token.as_bytes())
+        .unwrap_or(&[]);
+    if !secure_eq(token, secret.as_bytes()) {
+        warn!("Rejected Telegram webhook request with invalid secret token");
+        return Ok(status(StatusCode::UNAUTHORIZED));
+    }
+    let mut sender = match sender {
+        Some(sender) => sender,
+        None => return Ok(status(StatusCode::SERVICE_UNAVAILABLE)),
+    };
+
+    // Receive the request body, up to the maximum size
+    let mut body = request.into_body();
+    let mut data = vec![];
+    while let Some(chunk) = body.next().await {
+        match chunk {
+            Ok(chunk) if data.len() + chunk.len() > MAX_BODY_SIZE => {
+                warn!("Rejected Telegram webhook request, body is too large");
+                return Ok(status(StatusCode::PAYLOAD_TOO_LARGE));
+            }
+            Ok(chunk) => data.extend_from_slice(&chunk),
+            Err(err) => {
+                warn!("Failed to receive Telegram webhook request body: {}", err);
+                return Ok(status(StatusCode::BAD_REQUEST));
+            }
+        }
+    }
+
+    // Parse the update, skip updates we don't understand so Telegram doesn't resend them
+    let update: Update = match serde_json::from_slice(&data) {
+        Ok(update) => update,
+        Err(err) => {
+            warn!("Failed to parse Telegram webhook update, skipping: {}", err);
+            return Ok(status(StatusCode::OK));
+        }
+    };
+
+    // Queue the update for handling
+    if sender.send(update).await.is_err() {
+        error!("Failed to queue Telegram webhook update, update handler is gone");
+        return Ok(status(StatusCode::SERVICE_UNAVAILABLE));
+    }
+
+    Ok(status(StatusCode::OK))
+}
+
+/// Compare the given secrets, in constant time for secrets of equal length.
+///
+/// This doesn't leak how much of a guessed secret is correct through response timing.
+fn secure_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Build an empty response with the given status code.
+fn status(code: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = code;
+    response
+}
+
+/// Telegram API request to register a webhook.
+#[derive(Serialize, Debug)]
+struct SetWebhook {
+    url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    secret_token: Option<String>,
+}
+
+impl Request for SetWebhook {
+    type Type = JsonRequestType<Self>;
+    type Response = JsonTrueToUnitResponse;
+
+    fn serialize(&self) -> Result<TelegramRequest, RawError> {
+        Self::Type::serialize(RequestUrl::method("setWebhook"), self)
+    }
+}
+
+/// Telegram API request to remove a registered webhook.
+#[derive(Serialize, Debug)]
+struct DeleteWebhook {}
+
+impl Request for DeleteWebhook {
+    type Type = JsonRequestType<Self>;
+    type Response = JsonTrueToUnitResponse;
+
+    fn serialize(&self) -> Result<TelegramRequest, RawError> {
+        Self::Type::serialize(RequestUrl::method("deleteWebhook"), self)
+    }
+}
+
+/// A webhook listener error.
+#[derive(Debug)]
+pub enum Error {
+    /// The configured webhook URL is invalid.
+    Url(url::ParseError),
+
+    /// Failed to bind the webhook listener.
+    Bind(hyper::Error),
+
+    /// No secret token is configured, anyone could send forged updates.
+    NoSecret,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Url(err) => write!(f, "invalid webhook URL: {}", err),
+            Error::Bind(err) => write!(f, "failed to bind webhook listener: {}", err),
+            Error::NoSecret => write!(f, "no webhook secret token configured"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Url(err) => Some(err),
+            Error::Bind(err) => Some(err),
+            Error::NoSecret => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    /// Send a webhook request with the given secret token, while running or shutting down.
+    fn request(token: &str, running: bool) -> (StatusCode, Option<Update>) {
+        let update = r#"{"update_id": 1, "message": {"message_id": 1, "date": 0,
+            "chat": {"id": -1001, "type": "supergroup", "title": "Group"},
+            "from": {"id": 1, "is_bot": false, "first_name": "Spammer"}, "text": "Hi"}}"#;
+        let request = HttpRequest::post("/hook")
+            .header(SECRET_HEADER, token)
+            .body(Body::from(update))
+            .unwrap();
+        let (sender, mut receiver) = mpsc::channel(1);
+        let sender = if running { Some(sender) } else { None };
+
+        block_on(async {
+            let response = handle_request(request, "/hook".into(), "secret".into(), sender)
+                .await
+                .unwrap();
+            (response.status(), receiver.next().await)
+        })
+    }
+
+    #[test]
+    fn test_handle_request() {
+        let (status, update) = request("secret", true);
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(update.map(|update| update.id), Some(1));
+
+        let (status, update) = request("guess", true);
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(update.is_none());
+
+        // Telegram sends updates again later if refused while shutting down
+        let (status, update) = request("secret", false);
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(update.is_none());
+    }
+
+    #[test]
+    fn test_secure_eq() {
+        assert!(secure_eq(b"some-secret", b"some-secret"));
+        assert!(!secure_eq(b"some-secreT", b"some-secret"));
+        assert!(!secure_eq(b"some-secre", b"some-secret"));
+        assert!(!secure_eq(b"", b"some-secret"));
+    }
+}
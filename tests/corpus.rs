@@ -0,0 +1,52 @@
+//! Corpus regression suite.
+//!
+//! Audits all sample messages in `tests/corpus` against the fixture configuration, and reports
+//! any false positives and false negatives. Samples are illegal if their score reaches a threshold
+//! to take action at.
+
+use ban_binance_bot::{
+    config::{Action, Config},
+    corpus::{self, Expect},
+    scanner,
+};
+
+/// Fixture configuration to audit samples with.
+const CONFIG: &str = "./tests/fixtures/config.toml";
+
+/// Directory holding the corpus samples.
+const CORPUS: &str = "./tests/corpus";
+
+#[tokio::test]
+async fn corpus_verdicts() {
+    let config = Config::from_path(CONFIG).expect("failed to load fixture config");
+    let context = scanner::Context::new(&config);
+    let samples = corpus::load_dir(CORPUS).expect("failed to load corpus");
+    assert!(!samples.is_empty(), "corpus is empty");
+
+    // Audit each sample, collect unexpected verdicts
+    let mut false_positives = vec![];
+    let mut false_negatives = vec![];
+    for sample in &samples {
+        let verdict =
+            scanner::message::is_illegal_message(&config.scanner, &context, &sample.message())
+                .await;
+        let action = config.scanner.scoring.action(verdict.as_ref());
+        match (sample.expect, verdict) {
+            (Expect::Legal, Some(verdict)) if action != Action::Ignore => {
+                false_positives.push(format!("{}: {}", sample.name(), verdict))
+            }
+            (Expect::Illegal, _) if action == Action::Ignore => {
+                false_negatives.push(sample.name())
+            }
+            _ => {}
+        }
+    }
+
+    assert!(
+        false_positives.is_empty() && false_negatives.is_empty(),
+        "corpus regression in {} sample(s)\n\nfalse positives:\n{}\n\nfalse negatives:\n{}",
+        samples.len(),
+        false_positives.join("\n"),
+        false_negatives.join("\n"),
+    );
+}